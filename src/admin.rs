@@ -0,0 +1,48 @@
+use crate::prelude::*;
+use crate::cache::CacheHandle;
+use crate::config::Secret;
+
+use std::sync::Arc;
+use signal_hook::iterator::Signals;
+
+/// Compares in time independent of the position of the first difference
+fn token_matches(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len() && given.iter().zip(expected).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Checks `Authorization: Bearer <token>` against the configured token
+fn authorized(req: &HttpRequest, token: &Secret) -> bool {
+    req.headers()
+        .get(http::header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .is_some_and(|given| token_matches(given.as_bytes(), token.0.as_bytes()))
+}
+
+fn reload_handler(req: HttpRequest, cache: Data<CacheHandle>, token: &Secret) -> HttpResponse {
+    if !authorized(&req, token) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    CacheHandle::reload_in_background(cache.into_inner());
+    HttpResponse::Accepted().finish()
+}
+
+/// Reloads the cache whenever the process receives SIGHUP
+pub fn watch_sighup(cache: Arc<CacheHandle>) -> std::io::Result<()> {
+    let signals = Signals::new([signal_hook::SIGHUP])?;
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            CacheHandle::reload_in_background(cache.clone());
+        }
+    });
+    Ok(())
+}
+
+/// Registers admin endpoints, only when a token to protect them is configured
+pub fn register(app: &mut ServiceConfig, token: Option<Secret>) {
+    if let Some(token) = token {
+        app.service(web::resource("/reload").route(web::post().to(
+            move |req: HttpRequest, cache: Data<CacheHandle>| reload_handler(req, cache, &token),
+        )));
+    }
+}
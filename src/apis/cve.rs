@@ -1,13 +1,155 @@
 use crate::prelude::*;
+use super::{Api, ApiError, ApiVersion, filter_item_list, parse_date_field, since_filter};
+use crate::cache::{Cache, Cve, IStr};
 
 pub struct CveApi;
 
+/// Value of `Cve::source` for CVEs published by Red Hat
+const RH_SOURCE: &str = "Red Hat";
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CveReq {
     cve_list : Vec<String>,
     modified_since : Option<String>,
     published_since : Option<String>,
     #[serde(flatten)]
-    paging : Option<PagingInfo>,
+    paging : PagingInfo,
     rh_only : Option<bool>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CveDetail {
+    synopsis: String,
+    redhat_url: Option<String>,
+    secondary_url: Option<String>,
+    impact: Option<String>,
+    public_date: Option<String>,
+    modified_date: Option<String>,
+    cwe_list: Vec<String>,
+    cvss3_score: Option<f64>,
+    cvss3_metrics: Option<String>,
+    cvss2_score: Option<f64>,
+    cvss2_metrics: Option<String>,
+    description: Option<String>,
+    iava: Option<String>,
+    source: Option<String>,
+    package_list: Vec<Nevra>,
+    errata_list: Vec<IStr>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CveData {
+    cve_list: Map<String, CveDetail>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified_since: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    published_since: Option<String>,
+    #[serde(flatten)]
+    paging: PagingResp,
+}
+
+api_schema!(CveReq {
+    cve_list: Vec<String>,
+    modified_since: Option<String>,
+    published_since: Option<String>,
+    rh_only: Option<bool>,
+} flatten { paging: PagingInfo });
+api_schema!(CveDetail {
+    synopsis: String,
+    redhat_url: Option<String>,
+    secondary_url: Option<String>,
+    impact: Option<String>,
+    public_date: Option<String>,
+    modified_date: Option<String>,
+    cwe_list: Vec<String>,
+    cvss3_score: Option<f64>,
+    cvss3_metrics: Option<String>,
+    cvss2_score: Option<f64>,
+    cvss2_metrics: Option<String>,
+    description: Option<String>,
+    iava: Option<String>,
+    source: Option<String>,
+    package_list: Vec<Nevra>,
+    errata_list: Vec<IStr>,
+});
+api_schema!(CveData {
+    cve_list: Map<String, CveDetail>,
+    modified_since: Option<String>,
+    published_since: Option<String>,
+} flatten { paging: PagingResp });
+
+impl CveApi {
+    fn build_detail(cache: &Cache, name: &str, cve: &Cve) -> CveDetail {
+        CveDetail {
+            synopsis: name.to_string(),
+            redhat_url: cve.redhat_url.clone(),
+            secondary_url: cve.secondary_url.clone(),
+            impact: cve.impact.clone(),
+            public_date: cve.public_date.clone(),
+            modified_date: cve.modified_date.clone(),
+            cwe_list: cve.cwe_list.clone(),
+            cvss3_score: cve.cvss3_score,
+            cvss3_metrics: cve.cvss3_metrics.clone(),
+            cvss2_score: cve.cvss2_score,
+            cvss2_metrics: cve.cvss2_metrics.clone(),
+            description: cve.description.clone(),
+            iava: cve.iava.clone(),
+            source: cve.source.clone(),
+            package_list: cve
+                .pkg_ids
+                .iter()
+                .filter(|id| cache.pkg_details.contains_key(id))
+                .map(|id| cache.build_nevra(*id))
+                .collect(),
+            errata_list: cve
+                .errata_ids
+                .iter()
+                .filter_map(|id| cache.errataid_to_name.get(id).cloned())
+                .collect(),
+        }
+    }
+}
+
+impl Api for CveApi {
+    type PostReqType = CveReq;
+    type RespType = CveData;
+    const ENDPOINT_NAME: &'static str = "/cves";
+
+    fn process_list(cache: &Cache, data: Self::PostReqType, _version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let modified_since = parse_date_field("modified_since", &data.modified_since)?;
+        let published_since = parse_date_field("published_since", &data.published_since)?;
+
+        let mut cve_names = filter_item_list("cve_list", &data.cve_list, cache.cve_detail.keys())?;
+        cve_names.retain(|name| {
+            let cve = &cache.cve_detail[*name];
+            since_filter(cve.modified_date.as_deref(), modified_since.as_ref())
+                && since_filter(cve.public_date.as_deref(), published_since.as_ref())
+                && (!data.rh_only.unwrap_or(false) || cve.source.as_deref() == Some(RH_SOURCE))
+        });
+
+        let (cve_names, paging) = PagingInfo::paginate(&data.paging, cve_names);
+
+        let cve_list = cve_names
+            .into_iter()
+            .map(|name| (name.to_string(), Self::build_detail(cache, name, &cache.cve_detail[name])))
+            .collect();
+
+        Ok(CveData {
+            cve_list,
+            modified_since: data.modified_since,
+            published_since: data.published_since,
+            paging,
+        })
+    }
+
+    fn process_single(cache: &Cache, item: String, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let req = CveReq {
+            cve_list: vec![regex::escape(&item)],
+            modified_since: None,
+            published_since: None,
+            paging: PagingInfo::default(),
+            rh_only: None,
+        };
+        Self::process_list(cache, req, version)
+    }
+}
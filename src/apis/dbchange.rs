@@ -0,0 +1,14 @@
+use crate::prelude::*;
+use crate::cache::{CacheHandle, DbChange};
+use super::ApiError;
+
+pub const ENDPOINT_NAME: &str = "/dbchange";
+
+fn get_handler(cache: Data<CacheHandle>) -> Result<Json<DbChange>, ApiError> {
+    let cache = cache.get().ok_or_else(ApiError::unavailable)?;
+    Ok(Json(cache.dbchange.clone()))
+}
+
+pub fn register(app: &mut ServiceConfig) {
+    app.service(web::resource(ENDPOINT_NAME).route(web::get().to(get_handler)));
+}
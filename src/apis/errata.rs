@@ -0,0 +1,150 @@
+use crate::prelude::*;
+use super::{Api, ApiError, ApiVersion, filter_item_list, parse_date_field, since_filter};
+use crate::cache::{Cache, Errata, IStr, PkgId};
+
+pub struct ErrataApi;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ErrataReq {
+    errata_list: Vec<String>,
+    modified_since: Option<String>,
+    #[serde(rename = "type")]
+    errata_type: Option<String>,
+    severity: Option<String>,
+    #[serde(flatten)]
+    paging: PagingInfo,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrataDetail {
+    synopsis: Option<String>,
+    summary: Option<String>,
+    #[serde(rename = "type")]
+    errata_type: Option<String>,
+    severity: Option<String>,
+    description: Option<String>,
+    solution: Option<String>,
+    issued: Option<String>,
+    updated: Option<String>,
+    cve_list: Vec<IStr>,
+    package_list: Vec<Nevra>,
+    source_package_list: Vec<Nevra>,
+    bugzilla_list: Vec<String>,
+    reference_list: Vec<String>,
+    url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrataData {
+    errata_list: Map<String, ErrataDetail>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified_since: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    errata_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<String>,
+    #[serde(flatten)]
+    paging: PagingResp,
+}
+
+api_schema!(ErrataReq {
+    errata_list: Vec<String>,
+    modified_since: Option<String>,
+    errata_type as "type": Option<String>,
+    severity: Option<String>,
+} flatten { paging: PagingInfo });
+api_schema!(ErrataDetail {
+    synopsis: Option<String>,
+    summary: Option<String>,
+    errata_type as "type": Option<String>,
+    severity: Option<String>,
+    description: Option<String>,
+    solution: Option<String>,
+    issued: Option<String>,
+    updated: Option<String>,
+    cve_list: Vec<IStr>,
+    package_list: Vec<Nevra>,
+    source_package_list: Vec<Nevra>,
+    bugzilla_list: Vec<String>,
+    reference_list: Vec<String>,
+    url: Option<String>,
+});
+api_schema!(ErrataData {
+    errata_list: Map<String, ErrataDetail>,
+    modified_since: Option<String>,
+    errata_type as "type": Option<String>,
+    severity: Option<String>,
+} flatten { paging: PagingResp });
+
+impl ErrataApi {
+    fn build_nevras(cache: &Cache, pkg_ids: &[PkgId]) -> Vec<Nevra> {
+        pkg_ids
+            .iter()
+            .filter(|id| cache.pkg_details.contains_key(id))
+            .map(|id| cache.build_nevra(*id))
+            .collect()
+    }
+
+    fn build_detail(cache: &Cache, errata: &Errata) -> ErrataDetail {
+        ErrataDetail {
+            synopsis: errata.synopsis.clone(),
+            summary: errata.summary.clone(),
+            errata_type: errata.errata_type.clone(),
+            severity: errata.severity.clone(),
+            description: errata.description.clone(),
+            solution: errata.solution.clone(),
+            issued: errata.issued.clone(),
+            updated: errata.updated.clone(),
+            cve_list: errata.cve_list.clone(),
+            package_list: Self::build_nevras(cache, &errata.pkg_ids),
+            source_package_list: Self::build_nevras(cache, &errata.source_pkg_ids),
+            bugzilla_list: errata.bugzilla_list.clone(),
+            reference_list: errata.reference_list.clone(),
+            url: errata.url.clone(),
+        }
+    }
+}
+
+impl Api for ErrataApi {
+    type PostReqType = ErrataReq;
+    type RespType = ErrataData;
+    const ENDPOINT_NAME: &'static str = "/errata";
+
+    fn process_list(cache: &Cache, data: Self::PostReqType, _version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let modified_since = parse_date_field("modified_since", &data.modified_since)?;
+
+        let mut errata_names = filter_item_list("errata_list", &data.errata_list, cache.errata_detail.keys())?;
+        errata_names.retain(|name| {
+            let errata = &cache.errata_detail[*name];
+            since_filter(errata.modified.as_deref().or(errata.updated.as_deref()), modified_since.as_ref())
+                && (data.errata_type.is_none() || errata.errata_type == data.errata_type)
+                && (data.severity.is_none() || errata.severity == data.severity)
+        });
+
+        let (errata_names, paging) = PagingInfo::paginate(&data.paging, errata_names);
+
+        let errata_list = errata_names
+            .into_iter()
+            .map(|name| (name.to_string(), Self::build_detail(cache, &cache.errata_detail[name])))
+            .collect();
+
+        Ok(ErrataData {
+            errata_list,
+            modified_since: data.modified_since,
+            errata_type: data.errata_type,
+            severity: data.severity,
+            paging,
+        })
+    }
+
+    fn process_single(cache: &Cache, item: String, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let req = ErrataReq {
+            errata_list: vec![regex::escape(&item)],
+            modified_since: None,
+            errata_type: None,
+            severity: None,
+            paging: PagingInfo::default(),
+        };
+        Self::process_list(cache, req, version)
+    }
+}
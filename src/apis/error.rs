@@ -0,0 +1,84 @@
+use crate::prelude::*;
+use actix_web::http::StatusCode;
+
+/// Error returned from `Api` endpoints, rendered as JSON with message and the failing field
+#[derive(Debug)]
+pub enum ApiError {
+    /// Malformed input, maps to 400
+    BadRequest { message: String, field: Option<String> },
+    /// Well formed input, which does not make sense, maps to 422
+    Invalid { message: String, field: Option<String> },
+    /// Fault on our side, maps to 500
+    Internal { message: String },
+    /// Cache is not loaded yet, maps to 503
+    Unavailable { message: String },
+}
+
+#[derive(Debug, Serialize)]
+struct ApiErrorBody<'a> {
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<&'a str>,
+}
+
+impl ApiError {
+    pub fn bad_request(field: &str, message: impl ToString) -> Self {
+        ApiError::BadRequest { message: message.to_string(), field: Some(field.to_owned()) }
+    }
+
+    pub fn invalid(field: &str, message: impl ToString) -> Self {
+        ApiError::Invalid { message: message.to_string(), field: Some(field.to_owned()) }
+    }
+
+    pub fn internal(message: impl ToString) -> Self {
+        ApiError::Internal { message: message.to_string() }
+    }
+
+    pub fn unavailable() -> Self {
+        ApiError::Unavailable { message: "Cache is not loaded yet".to_owned() }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest { .. } => StatusCode::BAD_REQUEST,
+            ApiError::Invalid { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Internal { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Unavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn body(&self) -> ApiErrorBody<'_> {
+        match self {
+            ApiError::BadRequest { message, field } | ApiError::Invalid { message, field } => ApiErrorBody {
+                message,
+                field: field.as_ref().map(String::as_str),
+            },
+            ApiError::Internal { message } | ApiError::Unavailable { message } => ApiErrorBody { message, field: None },
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let body = self.body();
+        match body.field {
+            Some(field) => write!(f, "{}: {}", field, body.message),
+            None => write!(f, "{}", body.message),
+        }
+    }
+}
+
+impl Error for ApiError {}
+
+impl ResponseError for ApiError {
+    fn error_response(&self) -> HttpResponse {
+        if let ApiError::Internal { ref message } = self {
+            error!("Internal error: {}", message);
+        }
+        HttpResponse::build(self.status()).json(self.body())
+    }
+
+    fn render_response(&self) -> HttpResponse {
+        self.error_response()
+    }
+}
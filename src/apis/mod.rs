@@ -1,29 +1,138 @@
 use crate::prelude::*;
-use crate::cache::Cache;
+use crate::cache::{Cache, CacheHandle, IStr};
 
-use std::sync::RwLock;
+use crate::metrics::METRICS;
 
+use regex::Regex;
+use std::time::Instant;
+
+#[macro_use]
+pub mod openapi;
 pub mod updates;
 pub mod cve;
+pub mod errata;
 pub mod packages;
 pub mod repos;
+pub mod dbchange;
+pub mod vulnerabilities;
+pub mod pkgtree;
+pub mod patches;
+pub mod srpm_pkgtree;
+pub mod error;
+
+pub use self::error::ApiError;
+use self::openapi::ApiSchema;
+
+/// Returns all keys, which fully match any of the regular expressions in `patterns`, sorted.
+pub fn filter_item_list<'a>(
+    field: &str,
+    patterns: &[String],
+    keys: impl Iterator<Item = &'a IStr>,
+) -> Result<Vec<&'a str>, ApiError> {
+    if patterns.is_empty() {
+        return Err(ApiError::invalid(field, "list must not be empty"));
+    }
+    let patterns = patterns
+        .iter()
+        .map(|p| Regex::new(&format!("^(?:{})$", p)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ApiError::bad_request(field, e))?;
+
+    let mut res = keys
+        .filter(|k| patterns.iter().any(|p| p.is_match(k)))
+        .map(|k| &**k)
+        .collect::<Vec<_>>();
+
+    res.sort();
+    Ok(res)
+}
+
+/// Parses optional date from request field
+pub fn parse_date_field(field: &str, value: &Option<String>) -> Result<Option<DateTime<FixedOffset>>, ApiError> {
+    value
+        .as_ref()
+        .map(|d| parse_datetime(d).map_err(|e| ApiError::bad_request(field, e)))
+        .transpose()
+}
+
+/// Whether a dump entry dated `date` passes a `*_since` filter of the request.
+/// Entries without a date are never filtered out, nor are those with a date which can't be parsed,
+/// these are logged.
+pub fn since_filter(date: Option<&str>, since: Option<&DateTime<FixedOffset>>) -> bool {
+    match (date, since) {
+        (Some(date), Some(since)) => match parse_dump_datetime(date) {
+            Ok(date) => date >= *since,
+            Err(e) => {
+                warn!("Invalid date {:?} in the cache: {}", date, e);
+                true
+            }
+        },
+        _ => true,
+    }
+}
 
-fn post_handler<A : Api>((req, body, cache) : (HttpRequest, Json<A::PostReqType>, Data<RwLock<Cache>>)) -> Json<A::RespType> {
-    let cache = cache.get_ref().read().unwrap();
-    let res = A::process_list(&cache,body.into_inner());
-    res.map(Json).unwrap()
+/// Renders malformed JSON bodies as `ApiError`
+pub fn json_error_handler(err: actix_web::error::JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    ApiError::BadRequest { message: err.to_string(), field: None }.into()
+}
+
+/// Version of the API, selected by the `/api/vN` prefix.
+/// All versions share the implementation, they differ only in shape of responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ApiVersion {
+    V1,
+    V2,
+    V3,
+}
+
+impl ApiVersion {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/api/v1",
+            ApiVersion::V2 => "/api/v2",
+            ApiVersion::V3 => "/api/v3",
+        }
+    }
+}
+
+/// Runs `process` on the loaded cache and records the request, including requests
+/// rejected before processing, because the body is malformed or the cache is not loaded yet
+fn observe<A : Api>(cache : &CacheHandle, process : impl FnOnce(&Cache) -> Result<A::RespType, actix_web::Error>) -> Result<Json<A::RespType>, actix_web::Error> {
+    let start = Instant::now();
+    let res = match cache.get() {
+        Some(cache) => process(&cache),
+        None => Err(ApiError::unavailable().into()),
+    };
+    METRICS.observe_request(A::ENDPOINT_NAME, start.elapsed(), res.is_err());
+    res.map(Json)
+}
+
+fn post_handler<A : Api>((req, body, cache) : (HttpRequest, Result<Json<A::PostReqType>, actix_web::Error>, Data<CacheHandle>), version : ApiVersion) -> Result<Json<A::RespType>, actix_web::Error> {
+    observe::<A>(&cache, |cache| Ok(A::process_list(cache, body?.into_inner(), version)?))
+}
+
+fn get_handler<A : Api>((item, cache) : (Path<String>, Data<CacheHandle>), version : ApiVersion) -> Result<Json<A::RespType>, actix_web::Error> {
+    observe::<A>(&cache, |cache| Ok(A::process_single(cache, item.into_inner(), version)?))
 }
 
 pub trait Api : Sized + 'static {
-    type PostReqType : DeserializeOwned;
-    type RespType : Serialize;
+    type PostReqType : DeserializeOwned + ApiSchema;
+    type RespType : Serialize + ApiSchema;
     const ENDPOINT_NAME : &'static str;
 
-    fn process_list(cache : &Cache, req : Self::PostReqType) -> Result<Self::RespType>;
+    fn process_list(cache : &Cache, req : Self::PostReqType, version : ApiVersion) -> Result<Self::RespType, ApiError>;
+
+    /// Processes a single item taken from the url, as a list request with one entry.
+    /// Endpoints filtering by regular expressions match the item literally.
+    fn process_single(cache : &Cache, item : String, version : ApiVersion) -> Result<Self::RespType, ApiError>;
 
 
-    fn register(app : &mut ServiceConfig) -> Result<()> {
-        app.service(web::resource(Self::ENDPOINT_NAME).route(web::post().to(post_handler::<Self>)));
+    fn register(app : &mut ServiceConfig, version : ApiVersion) -> Result<()> {
+        app.service(web::resource(Self::ENDPOINT_NAME)
+            .route(web::post().to(move |args| post_handler::<Self>(args, version))));
+        app.service(web::resource(&format!("{}/{{item}}", Self::ENDPOINT_NAME))
+            .route(web::get().to(move |args| get_handler::<Self>(args, version))));
+        openapi::add_api::<Self>(version);
         Ok(())
     }
 }
\ No newline at end of file
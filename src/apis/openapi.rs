@@ -0,0 +1,256 @@
+use crate::prelude::*;
+use crate::cache::{IStr, Repo};
+use super::{Api, ApiVersion};
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Named schemas, collected while building the document, end up in `components/schemas`
+pub type SchemaDefs = BTreeMap<String, Value>;
+
+/// JSON schema of a type, as it is (de)serialized on the wire
+pub trait ApiSchema {
+    /// Field of this type may be left out of an object
+    const OPTIONAL: bool = false;
+
+    fn schema(defs: &mut SchemaDefs) -> Value;
+
+    /// Properties and required property names, for types which can be flattened into other objects
+    fn properties(_defs: &mut SchemaDefs) -> Option<(BTreeMap<String, Value>, Vec<String>)> {
+        None
+    }
+}
+
+macro_rules! primitive_schema {
+    ($type:expr, $($ty:ty),*) => {
+        $(impl ApiSchema for $ty {
+            fn schema(_defs: &mut SchemaDefs) -> Value {
+                json!({ "type": $type })
+            }
+        })*
+    };
+}
+
+primitive_schema!("string", String, IStr);
+primitive_schema!("boolean", bool);
+primitive_schema!("integer", usize, u64, i32);
+primitive_schema!("number", f64);
+
+impl<T: ApiSchema> ApiSchema for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn schema(defs: &mut SchemaDefs) -> Value {
+        match T::schema(defs) {
+            Value::Object(mut schema) if !schema.contains_key("$ref") => {
+                schema.insert("nullable".to_owned(), Value::Bool(true));
+                Value::Object(schema)
+            }
+            schema => json!({ "allOf": [schema], "nullable": true }),
+        }
+    }
+
+    fn properties(defs: &mut SchemaDefs) -> Option<(BTreeMap<String, Value>, Vec<String>)> {
+        T::properties(defs)
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Vec<T> {
+    fn schema(defs: &mut SchemaDefs) -> Value {
+        json!({ "type": "array", "items": T::schema(defs) })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Map<String, T> {
+    fn schema(defs: &mut SchemaDefs) -> Value {
+        json!({ "type": "object", "additionalProperties": T::schema(defs) })
+    }
+}
+
+impl ApiSchema for Nevra {
+    fn schema(_defs: &mut SchemaDefs) -> Value {
+        json!({ "type": "string", "example": "kernel-0:3.10.0-1062.el7.x86_64" })
+    }
+}
+
+/// Implements `ApiSchema` of a struct as a named object schema.
+/// Fields are listed with their types, `field as "name"` follows serde renames,
+/// `#[optional]` marks fields skipped by serde although their type is not `Option`
+/// and fields after `flatten` are merged into the object, as with `#[serde(flatten)]`.
+/// Listed fields must be exactly the fields of the struct, with the same types.
+/// Renames and skipping by serde are not checked, they have to be kept in sync by hand.
+macro_rules! api_schema {
+    ($ty:ident { $($(#[$optional:ident])? $field:ident $(as $rename:literal)? : $fty:ty),* $(,)? } $(flatten { $($ffield:ident : $flty:ty),* $(,)? })?) => {
+        impl $crate::apis::openapi::ApiSchema for $ty {
+            fn schema(defs: &mut $crate::apis::openapi::SchemaDefs) -> serde_json::Value {
+                let name = stringify!($ty);
+                if !defs.contains_key(name) {
+                    // Placeholder, so recursive types terminate
+                    defs.insert(name.to_owned(), serde_json::Value::Null);
+                    let (properties, required) = Self::properties(defs).unwrap_or_default();
+                    let mut schema = serde_json::json!({ "type": "object", "properties": properties });
+                    if !required.is_empty() {
+                        schema["required"] = serde_json::json!(required);
+                    }
+                    defs.insert(name.to_owned(), schema);
+                }
+                serde_json::json!({ "$ref": format!("#/components/schemas/{}", name) })
+            }
+
+            #[allow(unused_mut)]
+            fn properties(
+                defs: &mut $crate::apis::openapi::SchemaDefs,
+            ) -> Option<(std::collections::BTreeMap<String, serde_json::Value>, Vec<String>)> {
+                use $crate::apis::openapi::ApiSchema;
+                // Exhaustive destructuring, so adding a field to the struct without listing it fails to compile
+                let _check = |v: &$ty| {
+                    let $ty { $($field,)* $($($ffield,)*)? } = v;
+                    $(let _: &$fty = $field;)*
+                    $($(let _: &$flty = $ffield;)*)?
+                };
+
+                let mut properties = std::collections::BTreeMap::new();
+                let mut required = Vec::new();
+                $(
+                    let name = api_schema!(@name $field $($rename)?);
+                    properties.insert(name.to_owned(), <$fty>::schema(defs));
+                    if !(<$fty>::OPTIONAL $(|| api_schema!(@$optional))?) {
+                        required.push(name.to_owned());
+                    }
+                )*
+                $($(
+                    let (flat, flat_required) = <$flty>::properties(defs).unwrap_or_default();
+                    properties.extend(flat);
+                    if !<$flty>::OPTIONAL {
+                        required.extend(flat_required);
+                    }
+                )*)?
+                Some((properties, required))
+            }
+        }
+    };
+    (@optional) => { true };
+    (@name $field:ident) => { stringify!($field) };
+    (@name $field:ident $rename:literal) => { $rename };
+}
+
+api_schema!(PagingInfo { #[optional] page: usize, #[optional] page_size: usize });
+api_schema!(PagingResp { page: usize, page_size: usize, pages: usize });
+api_schema!(Repo {
+    label: IStr,
+    name: String,
+    url: String,
+    basearch: Option<String>,
+    releasever: Option<String>,
+    product: Option<String>,
+    product_id: Option<u64>,
+    revision: Option<String>,
+});
+
+/// Spec of one API version, filled by `Api::register`
+#[derive(Default)]
+struct Spec {
+    paths: BTreeMap<String, BTreeMap<String, Value>>,
+    schemas: SchemaDefs,
+}
+
+lazy_static::lazy_static! {
+    static ref SPECS: Mutex<BTreeMap<ApiVersion, Spec>> = Mutex::new(BTreeMap::new());
+}
+
+fn error_responses() -> Value {
+    let error = json!({
+        "description": "Error",
+        "content": { "application/json": { "schema": {
+            "type": "object",
+            "properties": { "message": { "type": "string" }, "field": { "type": "string" } },
+            "required": ["message"],
+        }}},
+    });
+    json!({ "400": error, "422": error, "500": error, "503": error })
+}
+
+/// Adds both operations of the endpoint, registering the same endpoint again has no effect
+pub fn add_api<A: Api>(version: ApiVersion) {
+    let mut specs = SPECS.lock().unwrap();
+    let spec = specs.entry(version).or_default();
+
+    let request = A::PostReqType::schema(&mut spec.schemas);
+    let response = A::RespType::schema(&mut spec.schemas);
+    let mut responses = error_responses();
+    responses["200"] = json!({
+        "description": "Success",
+        "content": { "application/json": { "schema": response } },
+    });
+    let id = A::ENDPOINT_NAME.trim_start_matches('/');
+
+    spec.paths.entry(A::ENDPOINT_NAME.to_owned()).or_default().insert("post".to_owned(), json!({
+        "operationId": format!("post_{}", id),
+        "requestBody": { "required": true, "content": { "application/json": { "schema": request } } },
+        "responses": responses,
+    }));
+    spec.paths.entry(format!("{}/{{item}}", A::ENDPOINT_NAME)).or_default().insert("get".to_owned(), json!({
+        "operationId": format!("get_{}", id),
+        "parameters": [{ "name": "item", "in": "path", "required": true, "schema": { "type": "string" } }],
+        "responses": responses,
+    }));
+}
+
+pub fn document(version: ApiVersion) -> Value {
+    let specs = SPECS.lock().unwrap();
+    let empty = Spec::default();
+    let spec = specs.get(&version).unwrap_or(&empty);
+    json!({
+        "openapi": "3.0.0",
+        "info": { "title": "webapp-rs", "version": env!("CARGO_PKG_VERSION") },
+        "servers": [{ "url": version.prefix() }],
+        "paths": spec.paths,
+        "components": { "schemas": spec.schemas },
+    })
+}
+
+/// Files of swagger-ui-dist needed by `DOCS_PAGE`, with their content types
+const DOCS_ASSETS: &[(&str, &str)] = &[
+    ("swagger-ui.css", "text/css; charset=utf-8"),
+    ("swagger-ui-bundle.js", "application/javascript; charset=utf-8"),
+];
+
+/// Viewer of the spec, its assets are served from the configured directory rather than a CDN
+const DOCS_PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+  <title>webapp-rs API</title>
+  <link rel="stylesheet" href="docs/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="docs/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });</script>
+</body>
+</html>
+"##;
+
+fn docs_asset_handler(file: Path<String>, assets: &std::path::Path) -> HttpResponse {
+    let content_type = match DOCS_ASSETS.iter().find(|(name, _)| *name == file.as_str()) {
+        Some((_, content_type)) => content_type,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    match std::fs::read(assets.join(file.as_str())) {
+        Ok(body) => HttpResponse::Ok().content_type(*content_type).body(body),
+        Err(e) => {
+            error!("Reading docs asset {:?} failed: {}", file.as_str(), e);
+            HttpResponse::NotFound().finish()
+        }
+    }
+}
+
+/// Serves the spec under the scope of `version`, and its viewer when `docs_assets` are configured
+pub fn register(app: &mut ServiceConfig, version: ApiVersion, docs_assets: Option<std::path::PathBuf>) {
+    app.service(web::resource("/openapi.json").route(web::get().to(move || HttpResponse::Ok().json(document(version)))));
+    if let Some(assets) = docs_assets {
+        app.service(web::resource("/docs").route(web::get().to(|| {
+            HttpResponse::Ok().content_type("text/html; charset=utf-8").body(DOCS_PAGE)
+        })));
+        app.service(web::resource("/docs/{file}").route(web::get().to(move |file| docs_asset_handler(file, &assets))));
+    }
+}
@@ -1,9 +1,105 @@
 use crate::prelude::*;
+use super::{Api, ApiError, ApiVersion};
+use crate::cache::{Cache, IStr, PkgId, Repo};
 
-pub struct CveApi;
+pub struct PackagesApi;
 
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PackagesReq {
     package_list : Vec<String>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct PackageDetail {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_package: Option<Nevra>,
+    /// Binary packages, filled for source packages only
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    package_list: Vec<Nevra>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    repositories: Vec<Repo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errata: Vec<IStr>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct PackagesData {
+    package_list: Map<String, PackageDetail>,
+}
+
+api_schema!(PackagesReq { package_list: Vec<String> });
+api_schema!(PackageDetail {
+    summary: Option<String>,
+    description: Option<String>,
+    source_package: Option<Nevra>,
+    #[optional] package_list: Vec<Nevra>,
+    #[optional] repositories: Vec<Repo>,
+    #[optional] errata: Vec<IStr>,
+});
+api_schema!(PackagesData { package_list: Map<String, PackageDetail> });
+
+impl PackagesApi {
+    fn build_detail(cache: &Cache, pkg_id: PkgId) -> PackageDetail {
+        let pkg = &cache.pkg_details[&pkg_id];
+
+        let source_package = pkg
+            .source_pkg_id
+            .filter(|id| cache.pkg_details.contains_key(id))
+            .map(|id| cache.build_nevra(id));
+
+        let repositories = cache
+            .pkgid_to_repoids
+            .get(&pkg_id)
+            .map(|ids| ids.iter().filter_map(|id| cache.repo_detail.get(id)).cloned().collect())
+            .unwrap_or_default();
+
+        let errata = cache
+            .pkgid_to_errataids
+            .get(&pkg_id)
+            .map(|ids| ids.iter().filter_map(|id| cache.errataid_to_name.get(id)).cloned().collect())
+            .unwrap_or_default();
+
+        PackageDetail {
+            summary: pkg.summary.clone(),
+            description: pkg.desc.clone(),
+            source_package,
+            package_list: cache.src_pkg_binaries(pkg_id),
+            repositories,
+            errata,
+        }
+    }
+}
+
+impl Api for PackagesApi {
+    type PostReqType = PackagesReq;
+    type RespType = PackagesData;
+    const ENDPOINT_NAME: &'static str = "/packages";
+
+    fn process_list(cache: &Cache, data: Self::PostReqType, _version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let mut response = PackagesData::default();
+
+        for pkg in data.package_list {
+            let nevra = Nevra::from_str(&pkg).map_err(|e| ApiError::bad_request("package_list", e))?;
+            let detail = cache
+                .nevra_pkg_id(&nevra)
+                .map(|pkg_id| Self::build_detail(cache, pkg_id))
+                .unwrap_or_default();
+
+            response.package_list.insert(pkg, detail);
+        }
+
+        Ok(response)
+    }
+
+    fn process_single(cache: &Cache, item: String, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let req = PackagesReq {
+            package_list: vec![item],
+        };
+        Self::process_list(cache, req, version)
+    }
+}
@@ -0,0 +1,57 @@
+use crate::prelude::*;
+use super::{Api, ApiError, ApiVersion};
+use super::updates::{UpdatesApi, UpdatesReq};
+use crate::cache::Cache;
+
+pub struct PatchesApi;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchDetail {
+    erratum: String,
+    #[serde(rename = "type")]
+    errata_type: Option<String>,
+    severity: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct PatchesData {
+    errata_list: Vec<PatchDetail>,
+}
+
+api_schema!(PatchDetail {
+    erratum: String,
+    errata_type as "type": Option<String>,
+    severity: Option<String>,
+});
+api_schema!(PatchesData { errata_list: Vec<PatchDetail> });
+
+impl Api for PatchesApi {
+    type PostReqType = UpdatesReq;
+    type RespType = PatchesData;
+    const ENDPOINT_NAME: &'static str = "/patches";
+
+    fn process_list(cache: &Cache, data: Self::PostReqType, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let updates = UpdatesApi::find_updates(cache, data, version)?;
+
+        let mut names = updates.applicable_errata().into_iter().collect::<Vec<_>>();
+        names.sort();
+
+        let errata_list = names
+            .into_iter()
+            .map(|name| {
+                let detail = cache.errata_detail.get(name);
+                PatchDetail {
+                    erratum: name.to_string(),
+                    errata_type: detail.and_then(|e| e.errata_type.clone()),
+                    severity: detail.and_then(|e| e.severity.clone()),
+                }
+            })
+            .collect();
+
+        Ok(PatchesData { errata_list })
+    }
+
+    fn process_single(cache: &Cache, item: String, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        Self::process_list(cache, UpdatesReq::from_package(item), version)
+    }
+}
@@ -0,0 +1,142 @@
+use crate::prelude::*;
+use super::{Api, ApiError, ApiVersion, filter_item_list};
+use crate::cache::{Cache, IStr, PkgId};
+
+pub struct PkgTreeApi;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PkgTreeReq {
+    package_name_list: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PkgTreeErrata {
+    name: IStr,
+    issued: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PkgTreeRepo {
+    label: IStr,
+    name: String,
+    basearch: Option<String>,
+    releasever: Option<String>,
+    revision: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PkgTreeItem {
+    nevra: Nevra,
+    first_published: Option<String>,
+    errata: Vec<PkgTreeErrata>,
+    repositories: Vec<PkgTreeRepo>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct PkgTreeData {
+    package_name_list: Map<String, Vec<PkgTreeItem>>,
+}
+
+api_schema!(PkgTreeReq { package_name_list: Vec<String> });
+api_schema!(PkgTreeErrata { name: IStr, issued: Option<String> });
+api_schema!(PkgTreeRepo {
+    label: IStr,
+    name: String,
+    basearch: Option<String>,
+    releasever: Option<String>,
+    revision: Option<String>,
+});
+api_schema!(PkgTreeItem {
+    nevra: Nevra,
+    first_published: Option<String>,
+    errata: Vec<PkgTreeErrata>,
+    repositories: Vec<PkgTreeRepo>,
+});
+api_schema!(PkgTreeData { package_name_list: Map<String, Vec<PkgTreeItem>> });
+
+impl PkgTreeApi {
+    fn build_item(cache: &Cache, pkg_id: PkgId) -> PkgTreeItem {
+        let errata = cache
+            .pkgid_to_errataids
+            .get(&pkg_id)
+            .map(|ids| ids.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|id| cache.errataid_to_name.get(id))
+            .map(|name| PkgTreeErrata {
+                name: name.clone(),
+                issued: cache.errata_detail.get(name).and_then(|e| e.issued.clone()),
+            })
+            .collect::<Vec<_>>();
+
+        let repositories = cache
+            .pkgid_to_repoids
+            .get(&pkg_id)
+            .map(|ids| ids.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|id| cache.repo_detail.get(id))
+            .map(|repo| PkgTreeRepo {
+                label: repo.label.clone(),
+                name: repo.name.clone(),
+                basearch: repo.basearch.clone(),
+                releasever: repo.releasever.clone(),
+                revision: repo.revision.clone(),
+            })
+            .collect();
+
+        // Package is first published by its earliest erratum
+        let first_published = errata
+            .iter()
+            .filter_map(|e| e.issued.as_ref())
+            .filter_map(|issued| parse_datetime(issued).ok().map(|date| (date, issued)))
+            .min_by_key(|(date, _)| *date)
+            .map(|(_, issued)| issued.clone());
+
+        PkgTreeItem {
+            nevra: cache.build_nevra(pkg_id),
+            first_published,
+            errata,
+            repositories,
+        }
+    }
+}
+
+impl Api for PkgTreeApi {
+    type PostReqType = PkgTreeReq;
+    type RespType = PkgTreeData;
+    const ENDPOINT_NAME: &'static str = "/pkgtree";
+
+    fn process_list(cache: &Cache, data: Self::PostReqType, _version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let mut response = PkgTreeData::default();
+        let names = filter_item_list("package_name_list", &data.package_name_list, cache.name_to_id.keys())?;
+
+        for name in names {
+            let name_id = cache.name_to_id[name];
+            let mut pkg_ids = cache
+                .updates
+                .get(&name_id)
+                .cloned()
+                .unwrap_or_default();
+            pkg_ids.retain(|id| cache.pkg_details.contains_key(id));
+            pkg_ids.sort_by(|a, b| {
+                let (a, b) = (&cache.pkg_details[a], &cache.pkg_details[b]);
+                cache.id_to_evr[&a.evr_id]
+                    .cmp(&cache.id_to_evr[&b.evr_id])
+                    .then_with(|| cache.id_to_arch[&a.arch_id].cmp(&cache.id_to_arch[&b.arch_id]))
+            });
+
+            let items = pkg_ids.into_iter().map(|id| Self::build_item(cache, id)).collect();
+            response.package_name_list.insert(name.to_string(), items);
+        }
+
+        Ok(response)
+    }
+
+    fn process_single(cache: &Cache, item: String, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let req = PkgTreeReq {
+            package_name_list: vec![regex::escape(&item)],
+        };
+        Self::process_list(cache, req, version)
+    }
+}
@@ -1,10 +1,75 @@
 use crate::prelude::*;
+use super::{Api, ApiError, ApiVersion, filter_item_list, parse_date_field, since_filter};
+use crate::cache::{Cache, Repo};
 
+pub struct ReposApi;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ReposReq {
     repository_list : Vec<String>,
     modified_since : Option<String>,
     #[serde(flatten)]
-    paging : Option<PagingInfo>
-}
\ No newline at end of file
+    paging : PagingInfo
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReposData {
+    repository_list: Map<String, Vec<Repo>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified_since: Option<String>,
+    #[serde(flatten)]
+    paging: PagingResp,
+}
+
+api_schema!(ReposReq {
+    repository_list: Vec<String>,
+    modified_since: Option<String>,
+} flatten { paging: PagingInfo });
+api_schema!(ReposData {
+    repository_list: Map<String, Vec<Repo>>,
+    modified_since: Option<String>,
+} flatten { paging: PagingResp });
+
+impl Api for ReposApi {
+    type PostReqType = ReposReq;
+    type RespType = ReposData;
+    const ENDPOINT_NAME: &'static str = "/repos";
+
+    fn process_list(cache: &Cache, data: Self::PostReqType, _version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let modified_since = parse_date_field("modified_since", &data.modified_since)?;
+
+        let labels = filter_item_list("repository_list", &data.repository_list, cache.repolabel_to_ids.keys())?;
+
+        let mut repos = Vec::new();
+        for label in labels {
+            let label_repos = cache.repolabel_to_ids[label]
+                .iter()
+                .filter_map(|id| cache.repo_detail.get(id))
+                .filter(|repo| since_filter(repo.revision.as_deref(), modified_since.as_ref()))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if !label_repos.is_empty() {
+                repos.push((label.to_string(), label_repos));
+            }
+        }
+
+        let (repos, paging) = PagingInfo::paginate(&data.paging, repos);
+        let repository_list = repos.into_iter().collect();
+
+        Ok(ReposData {
+            repository_list,
+            modified_since: data.modified_since,
+            paging,
+        })
+    }
+
+    fn process_single(cache: &Cache, item: String, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let req = ReposReq {
+            repository_list: vec![regex::escape(&item)],
+            modified_since: None,
+            paging: PagingInfo::default(),
+        };
+        Self::process_list(cache, req, version)
+    }
+}
@@ -0,0 +1,77 @@
+use crate::prelude::*;
+use super::{Api, ApiError, ApiVersion, filter_item_list};
+use crate::cache::{Cache, NameId, PkgId};
+
+pub struct SrpmPkgTreeApi;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SrpmPkgTreeReq {
+    package_name_list: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SrpmPkgTreeItem {
+    nevra: Nevra,
+    package_list: Vec<Nevra>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct SrpmPkgTreeData {
+    package_name_list: Map<String, Vec<SrpmPkgTreeItem>>,
+}
+
+api_schema!(SrpmPkgTreeReq { package_name_list: Vec<String> });
+api_schema!(SrpmPkgTreeItem { nevra: Nevra, package_list: Vec<Nevra> });
+api_schema!(SrpmPkgTreeData { package_name_list: Map<String, Vec<SrpmPkgTreeItem>> });
+
+impl Api for SrpmPkgTreeApi {
+    type PostReqType = SrpmPkgTreeReq;
+    type RespType = SrpmPkgTreeData;
+    const ENDPOINT_NAME: &'static str = "/srpm_pkgtree";
+
+    fn process_list(cache: &Cache, data: Self::PostReqType, _version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let mut response = SrpmPkgTreeData::default();
+        let names = filter_item_list("package_name_list", &data.package_name_list, cache.name_to_id.keys())?;
+
+        // Source packages are found through their binaries, `updates` need not list them
+        let mut src_pkg_ids_by_name = Map::<NameId, Vec<PkgId>>::default();
+        let name_ids = names.iter().map(|name| cache.name_to_id[*name]).collect::<Set<_>>();
+        for src_pkg_id in cache.src_pkg_id_to_pkg_ids.keys() {
+            if let Some(pkg) = cache.pkg_details.get(src_pkg_id) {
+                if name_ids.contains(&pkg.name_id) {
+                    src_pkg_ids_by_name.entry(pkg.name_id).or_default().push(*src_pkg_id);
+                }
+            }
+        }
+
+        for name in names {
+            let mut src_pkg_ids = match src_pkg_ids_by_name.remove(&cache.name_to_id[name]) {
+                Some(ids) => ids,
+                None => continue,
+            };
+            src_pkg_ids.sort_by(|a, b| {
+                cache.id_to_evr[&cache.pkg_details[a].evr_id]
+                    .cmp(&cache.id_to_evr[&cache.pkg_details[b].evr_id])
+                    .then(a.cmp(b))
+            });
+
+            let items = src_pkg_ids
+                .into_iter()
+                .map(|id| SrpmPkgTreeItem {
+                    nevra: cache.build_nevra(id),
+                    package_list: cache.src_pkg_binaries(id),
+                })
+                .collect();
+            response.package_name_list.insert(name.to_string(), items);
+        }
+
+        Ok(response)
+    }
+
+    fn process_single(cache: &Cache, item: String, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let req = SrpmPkgTreeReq {
+            package_name_list: vec![regex::escape(&item)],
+        };
+        Self::process_list(cache, req, version)
+    }
+}
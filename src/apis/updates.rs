@@ -1,6 +1,7 @@
 use crate::prelude::*;
-use super::Api;
-use crate::cache::Cache;
+use super::{Api, ApiError, ApiVersion};
+use crate::cache::{Cache, ErrataId, IStr, ModuleStream, NameId, PkgErrataId, PkgId, RepoId};
+use crate::metrics::METRICS;
 use std::collections::BTreeSet;
 
 pub struct UpdatesApi;
@@ -19,16 +20,35 @@ pub struct UpdatesReq {
     modules_list: Option<Vec<ModuleSpec>>,
     releasever: Option<String>,
     basearch: Option<String>,
+    /// Only honored in v3, reports updates also for packages not known to the cache
+    optimistic_updates: Option<bool>,
+}
+
+impl UpdatesReq {
+    /// Request for a single package, without any repository or module restrictions
+    pub fn from_package(package: String) -> Self {
+        UpdatesReq {
+            package_list: vec![package],
+            repository_list: None,
+            modules_list: None,
+            releasever: None,
+            basearch: None,
+            optimistic_updates: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct PkgUpdate {
     package: Nevra,
-    erratum: String,
+    erratum: IStr,
 
-    repository : Option<String>,
-    basearch : Option<String>,
-    releasever : Option<String>
+    repository : Option<IStr>,
+    /// Left out in v2, `null` in v1 and v3 when the repository does not specify it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    basearch : Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    releasever : Option<Option<String>>
 
 }
 
@@ -53,9 +73,52 @@ pub struct UpdatesData {
     basearch: Option<String>,
 }
 
+api_schema!(ModuleSpec { module_name: String, module_stream: String });
+api_schema!(UpdatesReq {
+    package_list: Vec<String>,
+    repository_list: Option<Vec<String>>,
+    modules_list: Option<Vec<ModuleSpec>>,
+    releasever: Option<String>,
+    basearch: Option<String>,
+    optimistic_updates: Option<bool>,
+});
+api_schema!(PkgUpdate {
+    package: Nevra,
+    erratum: IStr,
+    repository: Option<IStr>,
+    basearch: Option<Option<String>>,
+    releasever: Option<Option<String>>,
+});
+api_schema!(UpdatesPkgDetail {
+    summary: Option<String>,
+    description: Option<String>,
+    available_updates: Vec<PkgUpdate>,
+});
+api_schema!(UpdatesData {
+    update_list: Map<String, UpdatesPkgDetail>,
+    repository_list: Option<Vec<String>>,
+    releasever: Option<String>,
+    basearch: Option<String>,
+});
+
+impl UpdatesData {
+    /// Names of all errata, which provide at least one of the updates
+    pub fn applicable_errata(&self) -> Set<&str> {
+        self.update_list
+            .values()
+            .flat_map(|detail| detail.available_updates.iter())
+            .map(|update| &*update.erratum)
+            .collect()
+    }
+}
+
+/// Error of a cache row referenced by another table, which is not in the cache
+fn missing(table: &str, id: impl std::fmt::Display) -> ApiError {
+    ApiError::internal(format!("Inconsistent cache, {} has no entry {}", table, id))
+}
 
 impl UpdatesApi {
-    fn related_products(cache: &Cache, original_repo_ids: &Set<u64>) -> Set<u64> {
+    fn related_products(cache: &Cache, original_repo_ids: &Set<RepoId>) -> Set<u64> {
         let mut product_ids = Set::default();
         for original_pkg_repo_id in original_repo_ids.iter() {
             if let Some(ref pid) = cache.repo_detail[original_pkg_repo_id].product_id {
@@ -65,7 +128,7 @@ impl UpdatesApi {
         return product_ids;
     }
 
-    fn valid_releasevers(cache: &Cache, original_repo_ids: &Set<u64>) -> Set<String> {
+    fn valid_releasevers(cache: &Cache, original_repo_ids: &Set<RepoId>) -> Set<String> {
         let mut valid_releasevers = Set::default();
         for original_pkg_repo_id in original_repo_ids.iter() {
             if let Some(ref rv) = cache.repo_detail[original_pkg_repo_id].releasever {
@@ -75,62 +138,71 @@ impl UpdatesApi {
         return valid_releasevers;
     }
 
-    fn build_nevra(cache: &Cache, update_pkg_id: u64) -> Nevra {
-        let det = &cache.pkg_details[&update_pkg_id];
-        let name = &cache.id_to_name[&det.name_id];
-        let evr = &cache.id_to_evr[&det.evr_id];
-        let arh = &cache.id_to_arch[&det.arch_id];
-        return Nevra::from_name_evr_arch(name, evr.clone(), arh);
-    }
-
 
     fn get_repositories(
         cache: &Cache,
         product_ids: &Set<u64>,
-        update_pkg_id: u64,
-        errata_ids: &[u64],
-        available_repo_ids: &Set<u64>,
-        valid_releasevers: &Set<String>,
-    ) -> Set<u64> {
+        update_pkg_id: PkgId,
+        errata_ids: &[ErrataId],
+        available_repo_ids: &Set<RepoId>,
+        valid_releasevers: Option<&Set<String>>,
+    ) -> Result<Set<RepoId>, ApiError> {
         let mut errata_repo_ids = Set::default();
 
         for errata_id in errata_ids {
-            errata_repo_ids.extend(&cache.errataid_to_repoids[errata_id]);
+            let repo_ids = cache.errataid_to_repoids.get(errata_id).ok_or_else(|| missing("errataid_to_repoids", errata_id))?;
+            errata_repo_ids.extend(repo_ids);
         }
 
-        let mut repo_ids = Set::from_iter(&cache.pkgid_to_repoids[&update_pkg_id])
-                .intersection(&errata_repo_ids).map(|s| **s).collect::<Set<u64>>()
-                .intersection(available_repo_ids).map(|s| *s).collect::<Set<u64>>();
-
-
-        repo_ids.retain(|repo_id| {
-            valid_releasevers.contains(
-                cache.repo_detail[&repo_id]
-                    .releasever
-                    .as_ref()
-                    .unwrap_or(&String::new())
-                    .as_str(),
-            ) && errata_repo_ids.contains(&repo_id)
-        });
+        let pkg_repo_ids = cache.pkgid_to_repoids.get(&update_pkg_id).ok_or_else(|| missing("pkgid_to_repoids", update_pkg_id))?;
+        let repo_ids = Set::from_iter(pkg_repo_ids)
+                .intersection(&errata_repo_ids).map(|s| **s).collect::<Set<RepoId>>()
+                .intersection(available_repo_ids).map(|s| *s).collect::<Set<RepoId>>();
+
+        let mut res = Set::default();
+        for repo_id in repo_ids {
+            let repo = cache.repo_detail.get(&repo_id).ok_or_else(|| missing("repo_detail", repo_id))?;
+            let releasever = repo.releasever.as_deref().unwrap_or("");
+            if valid_releasevers.is_none_or(|valid| valid.contains(releasever)) && errata_repo_ids.contains(&repo_id) {
+                res.insert(repo_id);
+            }
+        }
+        Ok(res)
+    }
 
-        return repo_ids;
+    /// Packages of the same name with higher EVR, used when `nevra` itself is not known
+    fn optimistic_updates<'a>(cache: &'a Cache, name_id: NameId, nevra: &Nevra) -> &'a [PkgId] {
+        let evr = nevra.evr();
+        let pkg_ids = &cache.updates[&name_id];
+        let first_newer = pkg_ids
+            .iter()
+            .position(|id| cache.id_to_evr[&cache.pkg_details[id].evr_id].rpm_cmp(&evr) == std::cmp::Ordering::Greater)
+            .unwrap_or(pkg_ids.len());
+        &pkg_ids[first_newer..]
     }
 
     fn process_updates(
         cache: &Cache,
         packages_to_process: &Map<&str, Nevra>,
-        available_repo_ids: &Set<u64>,
+        available_repo_ids: &Set<RepoId>,
+        module_ids: &Set<u64>,
+        optimistic: bool,
+        version: ApiVersion,
         response: &mut UpdatesData,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), ApiError> {
         for (pkg, nevra) in packages_to_process.iter() {
             let nevra: &Nevra = nevra;
-            let name_id = if let Some(x) = cache.name_to_id.get(&nevra.name) {
+            let name_id = if let Some(x) = cache.name_to_id.get(nevra.name.as_str()) {
                 x
             } else {
                 continue;
             };
             let evr_id = cache.evr_to_id.get(&nevra.evr());
-            let arch_id = cache.arch_to_id.get(&nevra.arch).ok_or("Arch id not found".to_string())?;
+            let arch_id = if let Some(x) = cache.arch_to_id.get(nevra.arch.as_str()) {
+                x
+            } else {
+                continue;
+            };
 
             // If nothing is found, use empty list
             let current_evr_idxs: &[_] = evr_id
@@ -138,63 +210,53 @@ impl UpdatesApi {
                 .map(|v| v.as_ref())
                 .unwrap_or(&[][..]);
 
-            if current_evr_idxs.is_empty() {
-                //error!("package {:?} has no updates", name_id);
-                continue ;
-            }
-
-            let mut current_nevra_pkg_id = None;
+            let current_nevra_pkg_id = current_evr_idxs
+                .iter()
+                .map(|idx| cache.updates[&name_id][*idx as usize])
+                .find(|pkg_id| cache.pkg_details[pkg_id].arch_id == *arch_id);
+
+            let (update_pkg_ids, original_package_repo_ids) = match current_nevra_pkg_id {
+                Some(current_nevra_pkg_id) => {
+                    let resp_pkg_detail = response.update_list.entry(pkg.to_string()).or_default();
+                    if version == ApiVersion::V1 {
+                        resp_pkg_detail.summary = cache.pkg_details[&current_nevra_pkg_id].summary.clone();
+                        resp_pkg_detail.description = cache.pkg_details[&current_nevra_pkg_id].desc.clone();
+                    }
 
-            for current_evr_idx in current_evr_idxs {
-                //error!("current evr idx : => {:?}", current_evr_idx);
+                    let last_version_pkg_id = cache.updates[&name_id].last();
+                    if last_version_pkg_id == Some(&current_nevra_pkg_id) {
+                        continue;
+                    }
 
-                let pkg_id = cache.updates[&name_id][*current_evr_idx as usize];
-                let current_nevra_arch_id = &cache.pkg_details[&pkg_id].arch_id;
+                    let mut original_package_repo_ids = Set::default();
+                    if let Some(repoids) = cache.pkgid_to_repoids.get(&current_nevra_pkg_id) {
+                        original_package_repo_ids.extend(repoids.iter());
+                    }
 
-                //trace!("Package archs : {:?}, {:?}", current_nevra_arch_id, arch_id);
-                if current_nevra_arch_id == arch_id {
-                    current_nevra_pkg_id = Some(pkg_id);
-                    break ;
+                    let update_pkg_ids =
+                        &cache.updates[name_id][(*current_evr_idxs.last().unwrap() as usize) + 1..];
+                    (update_pkg_ids, Some(original_package_repo_ids))
                 }
-            }
-
-            if current_nevra_pkg_id.is_none() {
-                //error!("Package with NEVRA: {:?} not found", nevra);
-                continue;
-            } else {
-                //error!("Package with NEVRA: {:?} Found", nevra);
-            }
-
-            let current_nevra_pkg_id = current_nevra_pkg_id.unwrap();
-
-            let resp_pkg_detail = response.update_list.entry(pkg.to_string()).or_default();
-            // TODO: for api version 1 only
-            resp_pkg_detail.summary = cache.pkg_details[&current_nevra_pkg_id].summary.clone();
-            resp_pkg_detail.description = cache.pkg_details[&current_nevra_pkg_id].desc.clone();
-
-            let last_version_pkg_id = cache.updates[&name_id].last();
-            if last_version_pkg_id == Some(&current_nevra_pkg_id) {
-                //error!("Package is last, no updates");
-                continue ;
-            }
-
-            let mut original_package_repo_ids = Set::default();
-
-            if let Some(repoids) = cache.pkgid_to_repoids.get(&current_nevra_pkg_id) {
-                original_package_repo_ids.extend(repoids.iter());
-            }
+                // Package is not in the cache, so we know nothing about its repositories
+                None if optimistic => {
+                    response.update_list.entry(pkg.to_string()).or_default();
+                    (Self::optimistic_updates(cache, *name_id, nevra), None)
+                }
+                None => continue,
+            };
 
-            let product_ids = Self::related_products(cache, &original_package_repo_ids);
-            let valid_releasevers = Self::valid_releasevers(cache, &original_package_repo_ids);
-            //error!("Valid prods : {:#?}, valid vers : {:#?}", product_ids, valid_releasevers);
-            let update_pkg_ids =
-                &cache.updates[name_id][(*current_evr_idxs.last().unwrap() as usize) + 1..];
+            let product_ids = original_package_repo_ids
+                .as_ref()
+                .map(|ids| Self::related_products(cache, ids))
+                .unwrap_or_default();
+            let valid_releasevers = original_package_repo_ids
+                .as_ref()
+                .map(|ids| Self::valid_releasevers(cache, ids));
 
+            let resp_pkg_detail = response.update_list.get_mut(*pkg).unwrap();
             for update_pkg_id in update_pkg_ids {
-                //error!("Update pkg id : {:?}", update_pkg_id);
                 let errata_ids = &cache.pkgid_to_errataids.get(update_pkg_id);
                 if errata_ids.is_none() {
-                    //error!("Filtering out :{:?}, no errata", update_pkg_id);
                     continue
                 }
                 let errata_ids = errata_ids.unwrap();
@@ -202,33 +264,38 @@ impl UpdatesApi {
                 let updated_nevra_arch_id = cache.pkg_details[update_pkg_id].arch_id;
 
                 if updated_nevra_arch_id != *arch_id
-                    && !cache.arch_compat[&arch_id].contains(&updated_nevra_arch_id)
+                    && !cache.arch_compat.get(arch_id).ok_or_else(|| missing("arch_compat", arch_id))?.contains(&updated_nevra_arch_id)
                 {
-                    //error!("Filteroing out id : {:?}, wrong arch", update_pkg_id);
                     continue
                 }
 
-                let nevra = Self::build_nevra(cache, *update_pkg_id);
-               //error!("update nvera: {:?}", nevra);
+                let nevra = cache.build_nevra(*update_pkg_id);
                 for errata_id in errata_ids {
-                    let mut repo_ids = Self::get_repositories(
+                    if !Self::module_filter(cache, *update_pkg_id, *errata_id, module_ids) {
+                        continue;
+                    }
+                    let repo_ids = Self::get_repositories(
                         cache,
                         &product_ids,
                         *update_pkg_id,
                         &[*errata_id],
                         &available_repo_ids,
-                        &valid_releasevers,
-                    );
-                    //error!("Repoids  avail : {:#?} : {:#?}", available_repo_ids, repo_ids);
+                        valid_releasevers.as_ref(),
+                    )?;
 
                     for repo_id in repo_ids {
-                        let repo_det = &cache.repo_detail[&repo_id];
+                        let repo_det = cache.repo_detail.get(&repo_id).ok_or_else(|| missing("repo_detail", repo_id))?;
+                        let (basearch, releasever) = if version == ApiVersion::V2 {
+                            (None, None)
+                        } else {
+                            (Some(repo_det.basearch.clone()), Some(repo_det.releasever.clone()))
+                        };
                         resp_pkg_detail.available_updates.push(PkgUpdate {
                             package: nevra.clone(),
                             erratum: cache.errataid_to_name[errata_id].clone(),
                             repository : Some(repo_det.label.clone()),
-                            basearch : repo_det.basearch.clone(),
-                            releasever : repo_det.releasever.clone()
+                            basearch,
+                            releasever,
                         })
                     }
                 }
@@ -237,15 +304,39 @@ impl UpdatesApi {
         Ok(())
     }
 
+    /// Modular errata apply only when one of their module streams is enabled
+    fn module_filter(cache: &Cache, pkg_id: PkgId, errata_id: ErrataId, module_ids: &Set<u64>) -> bool {
+        match cache.pkgerrata_to_module.get(&PkgErrataId(pkg_id, errata_id)) {
+            Some(errata_modules) => errata_modules.iter().any(|m| module_ids.contains(m)),
+            None => true,
+        }
+    }
+
+    fn process_modules(cache: &Cache, data: &UpdatesReq) -> Set<u64> {
+        let mut module_ids = Set::default();
+        if let Some(ref modules_list) = data.modules_list {
+            for m in modules_list {
+                let key = ModuleStream {
+                    name: m.module_name.as_str().into(),
+                    stream: m.module_stream.as_str().into(),
+                };
+                if let Some(ids) = cache.modulename_to_id.get(&key) {
+                    module_ids.extend(ids);
+                }
+            }
+        }
+        module_ids
+    }
+
     fn process_repositories(
         cache: &Cache,
         data: &UpdatesReq,
         response: &mut UpdatesData,
-    ) -> Set<u64> {
+    ) -> Set<RepoId> {
         let mut available_repo_ids = Vec::new();
         if let Some(ref repos) = data.repository_list {
             for repo in repos {
-                if let Some(ids) = cache.repolabel_to_ids.get(repo) {
+                if let Some(ids) = cache.repolabel_to_ids.get(repo.as_str()) {
                     available_repo_ids.extend_from_slice(&ids)
                 }
             }
@@ -256,18 +347,18 @@ impl UpdatesApi {
 
         if let Some(ref releasever) = data.releasever {
             available_repo_ids.retain(|oid| {
-                !(cache.repo_detail[oid].releasever.as_ref() == Some(&releasever)
+                cache.repo_detail[oid].releasever.as_ref() == Some(&releasever)
                     || (cache.repo_detail[oid].releasever.is_none()
-                    && cache.repo_detail[oid].url.contains(releasever)))
+                    && cache.repo_detail[oid].url.contains(releasever))
             });
             response.releasever = Some(releasever.clone())
         }
 
         if let Some(ref basearch) = data.basearch {
             available_repo_ids.retain(|oid| {
-                !(cache.repo_detail[oid].basearch.as_ref() == Some(&basearch)
+                cache.repo_detail[oid].basearch.as_ref() == Some(&basearch)
                     || (cache.repo_detail[oid].basearch.is_none()
-                    && cache.repo_detail[oid].url.contains(basearch)))
+                    && cache.repo_detail[oid].url.contains(basearch))
             });
             response.basearch = Some(basearch.clone())
         }
@@ -278,42 +369,53 @@ impl UpdatesApi {
         cache: &'a Cache,
         data: &'a UpdatesReq,
         response: &mut UpdatesData,
-    ) -> Map<&'a str, Nevra> {
+    ) -> Result<Map<&'a str, Nevra>, ApiError> {
         let mut filtered_pkgs_to_process = Map::default();
 
         for pkg in &data.package_list {
-            let nevra = Nevra::from_str(&pkg).unwrap();
-            if let Some(id) = cache.name_to_id.get(&nevra.name) {
+            let nevra = Nevra::from_str(&pkg).map_err(|e| ApiError::bad_request("package_list", e))?;
+            if let Some(id) = cache.name_to_id.get(nevra.name.as_str()) {
                 if let Some(up) = cache.updates_index.get(id) {
                     filtered_pkgs_to_process.insert(pkg.as_str(), nevra);
                 }
             }
         }
 
-        filtered_pkgs_to_process
+        Ok(filtered_pkgs_to_process)
     }
-}
-
-impl Api for UpdatesApi {
-    type PostReqType = UpdatesReq;
-    type RespType = UpdatesData;
-    const ENDPOINT_NAME: &'static str = "/updates";
-
 
-    fn process_list(cache: &Cache, data: Self::PostReqType) -> Result<Self::RespType, Box<dyn Error>> {
+    /// Computes updates of the request, shared by the APIs, which derive their responses from updates
+    pub fn find_updates(cache: &Cache, data: UpdatesReq, version: ApiVersion) -> Result<UpdatesData, ApiError> {
         let mut response = UpdatesData::default();
         let available_repo_ids = Self::process_repositories(cache, &data, &mut response);
 
-        if let Some(ref modules_list) = data.modules_list {
-            for m in modules_list {}
-        }
-        let mut packages_to_process = Self::process_input_packages(cache, &data, &mut response);
+        let module_ids = Self::process_modules(cache, &data);
+        let mut packages_to_process = Self::process_input_packages(cache, &data, &mut response)?;
         Self::process_updates(
             cache,
             &packages_to_process,
             &available_repo_ids,
+            &module_ids,
+            version >= ApiVersion::V3 && data.optimistic_updates.unwrap_or(false),
+            version,
             &mut response,
-        );
+        )?;
         Ok(response)
     }
+}
+
+impl Api for UpdatesApi {
+    type PostReqType = UpdatesReq;
+    type RespType = UpdatesData;
+    const ENDPOINT_NAME: &'static str = "/updates";
+
+
+    fn process_list(cache: &Cache, data: Self::PostReqType, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        METRICS.observe_updates_package_list(data.package_list.len());
+        Self::find_updates(cache, data, version)
+    }
+
+    fn process_single(cache: &Cache, item: String, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        Self::process_list(cache, UpdatesReq::from_package(item), version)
+    }
 }
\ No newline at end of file
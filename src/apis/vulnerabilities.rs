@@ -0,0 +1,39 @@
+use crate::prelude::*;
+use super::{Api, ApiError, ApiVersion};
+use super::updates::{UpdatesApi, UpdatesReq};
+use crate::cache::{Cache, IStr};
+
+pub struct VulnerabilitiesApi;
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct VulnerabilitiesData {
+    cve_list: Vec<IStr>,
+}
+
+api_schema!(VulnerabilitiesData { cve_list: Vec<IStr> });
+
+impl Api for VulnerabilitiesApi {
+    type PostReqType = UpdatesReq;
+    type RespType = VulnerabilitiesData;
+    const ENDPOINT_NAME: &'static str = "/vulnerabilities";
+
+    fn process_list(cache: &Cache, data: Self::PostReqType, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        let updates = UpdatesApi::find_updates(cache, data, version)?;
+
+        let mut cve_list = updates
+            .applicable_errata()
+            .into_iter()
+            .filter_map(|name| cache.errata_detail.get(name))
+            .flat_map(|errata| errata.cve_list.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        cve_list.sort();
+        cve_list.dedup();
+        Ok(VulnerabilitiesData { cve_list })
+    }
+
+    fn process_single(cache: &Cache, item: String, version: ApiVersion) -> Result<Self::RespType, ApiError> {
+        Self::process_list(cache, UpdatesReq::from_package(item), version)
+    }
+}
@@ -1,250 +1,1017 @@
 use crate::prelude::*;
+use crate::rpm;
 
 use gnudbm::GdbmOpener;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
 use serde_aux::prelude::*;
 use std::io;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use rayon::prelude::*;
 
+fn invalid_key(s: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid key {:?}", s))
+}
+
+/// Parses one `:` separated part of a table key
+fn parse_key_part<T: FromStr>(part: Option<&str>, s: &str) -> Result<T, io::Error> {
+    part.and_then(|p| p.parse().ok()).ok_or_else(|| invalid_key(s))
+}
+
+/// Ordering follows rpm, EVRs which rpm considers equal, e.g. `1.01` and `1.1`, are ordered as strings,
+/// so that `Ord` agrees with `Eq`. Use `rpm_cmp` to compare by rpm rules alone.
+#[derive(Debug, Serialize, Deserialize, Clone, Hash, PartialEq, Eq)]
+pub struct Evr(pub u64, pub String, pub String);
 
-#[derive(Debug, Deserialize, Clone, Hash, PartialOrd, PartialEq, Eq)]
-pub struct Evr(
-    #[serde(deserialize_with = "deserialize_number_from_string")] pub u64,
-    pub String,
-    pub String,
+/// Evr as stored in the dump, where epoch may be either number or string
+#[derive(Debug, Deserialize)]
+struct PickleEvr(
+    #[serde(deserialize_with = "deserialize_number_from_string")] u64,
+    String,
+    String,
 );
 
+impl From<PickleEvr> for Evr {
+    fn from(evr: PickleEvr) -> Self {
+        Evr(evr.0, evr.1, evr.2)
+    }
+}
+
+impl Evr {
+    /// Compares as rpm does, `Equal` does not imply the EVRs are `==`
+    pub fn rpm_cmp(&self, other: &Self) -> std::cmp::Ordering {
+        rpm::evr_cmp((self.0, &self.1, &self.2), (other.0, &other.1, &other.2))
+    }
+}
+
+impl Ord for Evr {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.rpm_cmp(other)
+            .then_with(|| self.1.cmp(&other.1))
+            .then_with(|| self.2.cmp(&other.2))
+    }
+}
+
+impl PartialOrd for Evr {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl FromStr for Evr {
     type Err = io::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut parts: Vec<_> = s.split(':').collect::<Vec<_>>();
-        let release = parts.pop().unwrap();
-        let version = parts.pop().unwrap();
-        let epoch = parts.pop().unwrap();
+        let release = parts.pop().ok_or_else(|| invalid_key(s))?;
+        let version = parts.pop().ok_or_else(|| invalid_key(s))?;
         Ok(Evr(
-            epoch.parse().unwrap(),
+            parse_key_part(parts.pop(), s)?,
             version.to_string(),
             release.to_string(),
         ))
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Hash, PartialOrd, PartialEq, Eq)]
-pub struct NevraId(u64, u64, u64);
+/// Declares a compact id of one kind of cache entry, so ids of different tables can't be mixed up
+macro_rules! id_type {
+    ($name:ident) => {
+        #[derive(Debug, Serialize, Deserialize, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
+        #[serde(transparent)]
+        pub struct $name(pub u32);
+
+        impl FromStr for $name {
+            type Err = std::num::ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map($name)
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+id_type!(NameId);
+id_type!(EvrId);
+id_type!(ArchId);
+id_type!(PkgId);
+id_type!(RepoId);
+id_type!(ErrataId);
+
+/// String shared between all tables of the cache, which contain it
+pub type IStr = Arc<str>;
+
+/// Deduplicates strings, so each distinct value is stored once
+#[derive(Default)]
+struct Interner(Set<IStr>);
+
+impl Interner {
+    fn intern(&mut self, s: IStr) -> IStr {
+        if let Some(existing) = self.0.get(&*s) {
+            return existing.clone();
+        }
+        self.0.insert(s.clone());
+        s
+    }
+
+    fn intern_keys<K, V>(&mut self, map: Map<K, V>, f: impl Fn(&mut Self, K) -> K) -> Map<K, V>
+        where K: Eq + std::hash::Hash
+    {
+        map.into_iter().map(|(k, v)| (f(self, k), v)).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Hash, PartialOrd, PartialEq, Eq)]
+pub struct NevraId(NameId, EvrId, ArchId);
 
 impl FromStr for NevraId {
     type Err = io::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut parts: Vec<_> = s.split(':').collect::<Vec<_>>();
-        let arch = parts.pop().unwrap();
-        let evr = parts.pop().unwrap();
-        let name = parts.pop().unwrap();
-        Ok(NevraId(
-            name.parse().unwrap(),
-            evr.parse().unwrap(),
-            arch.parse().unwrap(),
-        ))
+        let arch = parse_key_part(parts.pop(), s)?;
+        let evr = parse_key_part(parts.pop(), s)?;
+        let name = parse_key_part(parts.pop(), s)?;
+        Ok(NevraId(name, evr, arch))
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Hash, PartialOrd, PartialEq, Eq)]
+pub struct PkgErrataId(pub PkgId, pub ErrataId);
+
+impl FromStr for PkgErrataId {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<_> = s.split(':').collect::<Vec<_>>();
+        let errata = parse_key_part(parts.pop(), s)?;
+        let pkg = parse_key_part(parts.pop(), s)?;
+        Ok(PkgErrataId(pkg, errata))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Hash, PartialOrd, PartialEq, Eq)]
+pub struct ModuleStream {
+    pub name: IStr,
+    pub stream: IStr,
+}
 
-#[derive(Debug, Deserialize, Clone, Hash)]
+impl FromStr for ModuleStream {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, stream) = s.split_at(s.find(':').ok_or_else(|| invalid_key(s))?);
+        Ok(ModuleStream {
+            name: name.into(),
+            stream: stream[1..].into(),
+        })
+    }
+}
+
+
+#[derive(Debug, Serialize, Deserialize, Clone, Hash)]
 pub struct Package {
-    pub name_id: u64,
-    pub evr_id: u64,
-    pub arch_id: u64,
+    pub name_id: NameId,
+    pub evr_id: EvrId,
+    pub arch_id: ArchId,
     pub summary: Option<String>,
     pub desc: Option<String>,
-    pub source_pkg_id: Option<u64>,
+    pub source_pkg_id: Option<PkgId>,
 }
 
-#[derive(Debug, Deserialize, Clone, Hash)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Cve {
-    //id: String,
+    pub redhat_url: Option<String>,
+    pub secondary_url: Option<String>,
+    pub cvss3_score: Option<f64>,
+    pub cvss3_metrics: Option<String>,
+    pub impact: Option<String>,
+    pub public_date: Option<String>,
+    pub modified_date: Option<String>,
+    pub iava: Option<String>,
+    pub description: Option<String>,
+    pub cwe_list: Vec<String>,
+    pub pkg_ids: Vec<PkgId>,
+    pub errata_ids: Vec<ErrataId>,
+    pub cvss2_score: Option<f64>,
+    pub cvss2_metrics: Option<String>,
+    pub source: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone, Hash)]
+#[derive(Debug, Deserialize, Serialize, Clone, Hash)]
 pub struct Repo {
-    pub label: String,
+    pub label: IStr,
     pub name: String,
     pub url: String,
     pub basearch: Option<String>,
     pub releasever: Option<String>,
     pub product: Option<String>,
     pub product_id: Option<u64>,
-    //revision : Option<u64>
+    pub revision: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone, Hash)]
-pub struct Errata {}
+#[derive(Debug, Serialize, Deserialize, Clone, Hash)]
+pub struct Errata {
+    pub synopsis: Option<String>,
+    pub summary: Option<String>,
+    pub errata_type: Option<String>,
+    pub severity: Option<String>,
+    pub description: Option<String>,
+    pub solution: Option<String>,
+    pub issued: Option<String>,
+    pub updated: Option<String>,
+    pub cve_list: Vec<IStr>,
+    pub bugzilla_list: Vec<String>,
+    pub reference_list: Vec<String>,
+    pub pkg_ids: Vec<PkgId>,
+    pub source_pkg_ids: Vec<PkgId>,
+    pub modified: Option<String>,
+    pub url: Option<String>,
+}
+
+/// Timestamps of the dump export and of the last changes of its content
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DbChange {
+    pub exported: Option<DateTime<FixedOffset>>,
+    pub last_change: Option<DateTime<FixedOffset>>,
+    pub errata_changes: Option<DateTime<FixedOffset>>,
+    pub cve_changes: Option<DateTime<FixedOffset>>,
+    pub repository_changes: Option<DateTime<FixedOffset>>,
+}
 
-#[derive(Debug, Deserialize, Clone)]
+impl DbChange {
+    /// Timestamps are informational, so entries which can't be used are logged and skipped
+    /// instead of failing the load
+    fn insert(&mut self, key: &str, value: pickle::Value) {
+        let field = match key {
+            "exported" => &mut self.exported,
+            "last_change" => &mut self.last_change,
+            "errata_changes" => &mut self.errata_changes,
+            "cve_changes" => &mut self.cve_changes,
+            "repository_changes" => &mut self.repository_changes,
+            other => {
+                warn!("Unknown dbchange entry {:?}", other);
+                return;
+            }
+        };
+        let date = match value {
+            pickle::Value::String(ref s) => parse_dump_datetime(s),
+            _ => {
+                warn!("Invalid dbchange entry {:?}: {:?} is not a timestamp", key, value);
+                return;
+            }
+        };
+        match date {
+            Ok(date) => *field = Some(date),
+            Err(e) => warn!("Invalid dbchange entry {:?}: {:?}: {}", key, value, e),
+        }
+    }
+
+    fn merge(&mut self, other: DbChange) {
+        self.exported = self.exported.or(other.exported);
+        self.last_change = self.last_change.or(other.last_change);
+        self.errata_changes = self.errata_changes.or(other.errata_changes);
+        self.cve_changes = self.cve_changes.or(other.cve_changes);
+        self.repository_changes = self.repository_changes.or(other.repository_changes);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UpdatesIndex {
-    #[serde(flatten)]
-    pub data: Map<u64, Vec<u64>>,
+    /// Positions in the `updates` list of the package name, for each of its EVRs
+    pub data: Map<EvrId, Vec<u32>>,
 }
 
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Cache {
-    pub name_to_id: Map<String, u64>,
-    pub id_to_name: Map<u64, String>,
-    pub updates: Map<u64, Vec<u64>>,
-    pub updates_index: Map<u64, UpdatesIndex>,
-    pub evr_to_id: Map<Evr, u64>,
-    pub id_to_evr: Map<u64, Evr>,
-    pub arch_to_id: Map<String, u64>,
-    pub id_to_arch: Map<u64, String>,
-    pub arch_compat: Map<u64, Vec<u64>>,
-
-    pub pkg_details: Map<u64, Package>,
-    pub nevra_to_pkgid: Map<NevraId, u64>,
-    pub repo_detail: Map<u64, Repo>,
-    pub repolabel_to_ids: Map<String, Vec<u64>>,
-    pub productid_to_repoids: Map<u64, Vec<u64>>,
-    pub pkgid_to_repoids: Map<u64, Vec<u64>>,
-    pub errataid_to_name: Map<u64, String>,
-    pub pkgid_to_errataids: Map<u64, Vec<u64>>,
-    pub errataid_to_repoids: Map<u64, Vec<u64>>,
-    pub cve_detail: Map<String, Cve>,
-    pub dbchange: Map<String, String>,
-    pub errata_detail: Map<String, Errata>,
-    pub pkgerrata_to_module: Map<String, String>,
-    pub modulename_to_id: Map<String, String>,
-    pub src_pkg_id_to_pkg_ids: Map<String, Vec<u64>>,
-}
-
-pub fn load(name: String) -> Result<Cache, Box<dyn Error>> {
-    let file = PathBuf::from(name);
-    let db = GdbmOpener::new().readonly(&file).expect("Opening failed");
+    pub name_to_id: Map<IStr, NameId>,
+    pub id_to_name: Map<NameId, IStr>,
+    pub updates: Map<NameId, Vec<PkgId>>,
+    pub updates_index: Map<NameId, UpdatesIndex>,
+    pub evr_to_id: Map<Evr, EvrId>,
+    pub id_to_evr: Map<EvrId, Evr>,
+    pub arch_to_id: Map<IStr, ArchId>,
+    pub id_to_arch: Map<ArchId, IStr>,
+    pub arch_compat: Map<ArchId, Vec<ArchId>>,
 
-    let mut cache = Cache::default();
-    for (key, data) in db.iter() {
-        let kstr = std::str::from_utf8(key.as_bytes()).unwrap();
-        let (key, id) = kstr.split_at(kstr.find(':').unwrap());
-        let (_, id) = id.split_at(1);
-        let data = data.as_bytes();
-
-        match key {
-            "packagename2id" => {
-                cache
-                    .name_to_id
-                    .insert(id.to_owned(), pickle::from_slice::<u64>(data).unwrap());
-                //println!("{:?} = {:?}", id, cache.name_to_id[id])
-            }
-            "id2packagename" => {
-                cache
-                    .id_to_name
-                    .insert(id.parse()?, pickle::from_slice::<String>(data)?);
-            }
-            "updates" => {
-                cache.updates.insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "updates_index" => {
-                cache
-                    .updates_index
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "evr2id" => {
-                cache
-                    .evr_to_id
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "id2evr" => {
-                cache
-                    .id_to_evr
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "arch2id" => {
-                cache
-                    .arch_to_id
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "id2arch" => {
-                cache
-                    .id_to_arch
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "arch_compat" => {
-                cache
-                    .arch_compat
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "package_details" => {
-                cache
-                    .pkg_details
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "nevra2pkgid" => {
-                cache
-                    .nevra_to_pkgid
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "repo_detail" => {
-                let id = id.parse()?;
-                cache.repo_detail.insert(id, pickle::from_slice(data)?);
-            }
-            "repolabel2ids" => {
-                cache
-                    .repolabel_to_ids
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "productid2repoids" => {
-                cache
-                    .productid_to_repoids
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "pkgid2repoids" => {
-                cache
-                    .pkgid_to_repoids
-                    .insert(id.parse()?, pickle::from_slice(data)?);
-            }
-            "errataid2name" => {
-                cache
-                    .errataid_to_name
-                    .insert(id.parse()?, pickle::from_slice(data)?);
+    pub pkg_details: Map<PkgId, Package>,
+    pub nevra_to_pkgid: Map<NevraId, PkgId>,
+    pub repo_detail: Map<RepoId, Repo>,
+    pub repolabel_to_ids: Map<IStr, Vec<RepoId>>,
+    pub productid_to_repoids: Map<u64, Vec<RepoId>>,
+    pub pkgid_to_repoids: Map<PkgId, Vec<RepoId>>,
+    pub errataid_to_name: Map<ErrataId, IStr>,
+    pub pkgid_to_errataids: Map<PkgId, Vec<ErrataId>>,
+    pub errataid_to_repoids: Map<ErrataId, Vec<RepoId>>,
+    pub cve_detail: Map<IStr, Cve>,
+    pub dbchange: DbChange,
+    pub errata_detail: Map<IStr, Errata>,
+    pub pkgerrata_to_module: Map<PkgErrataId, Vec<u64>>,
+    pub modulename_to_id: Map<ModuleStream, Vec<u64>>,
+    pub src_pkg_id_to_pkg_ids: Map<PkgId, Vec<PkgId>>,
+}
+
+impl Cache {
+    pub fn build_nevra(&self, pkg_id: PkgId) -> Nevra {
+        let det = &self.pkg_details[&pkg_id];
+        let name = &self.id_to_name[&det.name_id];
+        let evr = &self.id_to_evr[&det.evr_id];
+        let arch = &self.id_to_arch[&det.arch_id];
+        Nevra::from_name_evr_arch(&**name, evr.clone(), &**arch)
+    }
+
+    /// Binary packages built from the source package
+    pub fn src_pkg_binaries(&self, src_pkg_id: PkgId) -> Vec<Nevra> {
+        let mut nevras = self
+            .src_pkg_id_to_pkg_ids
+            .get(&src_pkg_id)
+            .map(|ids| ids.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter(|id| self.pkg_details.contains_key(id))
+            .map(|id| self.build_nevra(*id))
+            .collect::<Vec<_>>();
+        nevras.sort();
+        nevras
+    }
+
+    pub fn nevra_pkg_id(&self, nevra: &Nevra) -> Option<PkgId> {
+        let name_id = self.name_to_id.get(nevra.name.as_str())?;
+        let evr_id = self.evr_to_id.get(&nevra.evr())?;
+        let arch_id = self.arch_to_id.get(nevra.arch.as_str())?;
+        self.nevra_to_pkgid
+            .get(&NevraId(*name_id, *evr_id, *arch_id))
+            .cloned()
+    }
+
+    /// Number of entries of each table, by field name
+    pub fn table_sizes(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("name_to_id", self.name_to_id.len()),
+            ("id_to_name", self.id_to_name.len()),
+            ("updates", self.updates.len()),
+            ("updates_index", self.updates_index.len()),
+            ("evr_to_id", self.evr_to_id.len()),
+            ("id_to_evr", self.id_to_evr.len()),
+            ("arch_to_id", self.arch_to_id.len()),
+            ("id_to_arch", self.id_to_arch.len()),
+            ("arch_compat", self.arch_compat.len()),
+            ("pkg_details", self.pkg_details.len()),
+            ("nevra_to_pkgid", self.nevra_to_pkgid.len()),
+            ("repo_detail", self.repo_detail.len()),
+            ("repolabel_to_ids", self.repolabel_to_ids.len()),
+            ("productid_to_repoids", self.productid_to_repoids.len()),
+            ("pkgid_to_repoids", self.pkgid_to_repoids.len()),
+            ("errataid_to_name", self.errataid_to_name.len()),
+            ("pkgid_to_errataids", self.pkgid_to_errataids.len()),
+            ("errataid_to_repoids", self.errataid_to_repoids.len()),
+            ("cve_detail", self.cve_detail.len()),
+            ("errata_detail", self.errata_detail.len()),
+            ("pkgerrata_to_module", self.pkgerrata_to_module.len()),
+            ("modulename_to_id", self.modulename_to_id.len()),
+            ("src_pkg_id_to_pkg_ids", self.src_pkg_id_to_pkg_ids.len()),
+        ]
+    }
+
+    /// Moves all entries of `other` into this cache
+    fn merge(&mut self, other: Cache) {
+        merge_map(&mut self.name_to_id, other.name_to_id);
+        merge_map(&mut self.id_to_name, other.id_to_name);
+        merge_map(&mut self.updates, other.updates);
+        merge_map(&mut self.updates_index, other.updates_index);
+        merge_map(&mut self.evr_to_id, other.evr_to_id);
+        merge_map(&mut self.id_to_evr, other.id_to_evr);
+        merge_map(&mut self.arch_to_id, other.arch_to_id);
+        merge_map(&mut self.id_to_arch, other.id_to_arch);
+        merge_map(&mut self.arch_compat, other.arch_compat);
+        merge_map(&mut self.pkg_details, other.pkg_details);
+        merge_map(&mut self.nevra_to_pkgid, other.nevra_to_pkgid);
+        merge_map(&mut self.repo_detail, other.repo_detail);
+        merge_map(&mut self.repolabel_to_ids, other.repolabel_to_ids);
+        merge_map(&mut self.productid_to_repoids, other.productid_to_repoids);
+        merge_map(&mut self.pkgid_to_repoids, other.pkgid_to_repoids);
+        merge_map(&mut self.errataid_to_name, other.errataid_to_name);
+        merge_map(&mut self.pkgid_to_errataids, other.pkgid_to_errataids);
+        merge_map(&mut self.errataid_to_repoids, other.errataid_to_repoids);
+        merge_map(&mut self.cve_detail, other.cve_detail);
+        self.dbchange.merge(other.dbchange);
+        merge_map(&mut self.errata_detail, other.errata_detail);
+        merge_map(&mut self.pkgerrata_to_module, other.pkgerrata_to_module);
+        merge_map(&mut self.modulename_to_id, other.modulename_to_id);
+        merge_map(&mut self.src_pkg_id_to_pkg_ids, other.src_pkg_id_to_pkg_ids);
+    }
+
+    /// Makes all tables share a single copy of each name, label and advisory string.
+    /// Rows are decoded independently, so this runs once the whole cache is loaded.
+    fn intern_strings(&mut self) {
+        let mut interner = Interner::default();
+        let i = &mut interner;
+
+        self.name_to_id = i.intern_keys(std::mem::take(&mut self.name_to_id), Interner::intern);
+        self.id_to_name.values_mut().for_each(|n| *n = i.intern(n.clone()));
+        self.arch_to_id = i.intern_keys(std::mem::take(&mut self.arch_to_id), Interner::intern);
+        self.id_to_arch.values_mut().for_each(|a| *a = i.intern(a.clone()));
+
+        self.repolabel_to_ids = i.intern_keys(std::mem::take(&mut self.repolabel_to_ids), Interner::intern);
+        self.repo_detail.values_mut().for_each(|r| r.label = i.intern(r.label.clone()));
+
+        self.cve_detail = i.intern_keys(std::mem::take(&mut self.cve_detail), Interner::intern);
+        self.errata_detail = i.intern_keys(std::mem::take(&mut self.errata_detail), Interner::intern);
+        self.errataid_to_name.values_mut().for_each(|n| *n = i.intern(n.clone()));
+        for errata in self.errata_detail.values_mut() {
+            errata.cve_list.iter_mut().for_each(|c| *c = i.intern(c.clone()));
+        }
+
+        self.modulename_to_id = i.intern_keys(std::mem::take(&mut self.modulename_to_id), |i, m| ModuleStream {
+            name: i.intern(m.name),
+            stream: i.intern(m.stream),
+        });
+    }
+
+    /// Sanity check of freshly loaded cache, before it starts serving requests
+    pub fn check(&self) -> Result<()> {
+        if self.name_to_id.is_empty() || self.pkg_details.is_empty() || self.repo_detail.is_empty() {
+            return Err("Cache is missing package or repository data".into());
+        }
+        for (name_id, pkg_ids) in self.updates.iter() {
+            if !self.updates_index.contains_key(name_id) {
+                return Err(format!("Package name {} has no updates index", name_id).into());
             }
-            "pkgid2errataids" => {
-                cache
-                    .pkgid_to_errataids
-                    .insert(id.parse()?, pickle::from_slice(data)?);
+            if let Some(pkg_id) = pkg_ids.iter().find(|id| !self.pkg_details.contains_key(id)) {
+                return Err(format!("Package {} has no details", pkg_id).into());
             }
-            "errataid2repoids" => {
-                cache
-                    .errataid_to_repoids
-                    .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        Ok(())
+    }
+}
+
+/// Cache together with information about its loading
+struct LoadedCache {
+    cache: Arc<Cache>,
+    loaded_at: DateTime<Utc>,
+    load_duration: Duration,
+}
+
+/// State of the served cache, as reported by readiness checks
+#[derive(Debug, Serialize)]
+pub struct CacheStatus {
+    /// Cache is loaded and the last reload did not fail
+    pub ready: bool,
+    pub path: PathBuf,
+    pub reloading: bool,
+    pub loaded_at: Option<DateTime<Utc>>,
+    pub load_duration: Option<f64>,
+    pub exported: Option<DateTime<FixedOffset>>,
+    /// Error of the last failed load, cleared by a successful one
+    pub error: Option<String>,
+    pub tables: std::collections::BTreeMap<&'static str, usize>,
+}
+
+/// Shared access to current cache, which can be replaced while the server is running.
+/// Requests hold their own `Arc` of the cache, so they finish against the snapshot they started with.
+pub struct CacheHandle {
+    path: PathBuf,
+    opts: LoadOptions,
+    current: RwLock<Option<LoadedCache>>,
+    last_error: RwLock<Option<String>>,
+    reloading: AtomicBool,
+}
+
+impl CacheHandle {
+    /// Creates a handle without cache, which becomes available after the first `reload`
+    pub fn new(path: impl Into<PathBuf>, opts: LoadOptions) -> Self {
+        CacheHandle {
+            path: path.into(),
+            opts,
+            current: RwLock::new(None),
+            last_error: RwLock::new(None),
+            reloading: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the current cache, `None` until it is loaded for the first time
+    pub fn get(&self) -> Option<Arc<Cache>> {
+        self.current.read().unwrap().as_ref().map(|c| c.cache.clone())
+    }
+
+    pub fn status(&self) -> CacheStatus {
+        let current = self.current.read().unwrap();
+        let error = self.last_error.read().unwrap().clone();
+        CacheStatus {
+            ready: current.is_some() && error.is_none(),
+            path: self.path.clone(),
+            reloading: self.reloading.load(Ordering::SeqCst),
+            loaded_at: current.as_ref().map(|c| c.loaded_at),
+            load_duration: current.as_ref().map(|c| c.load_duration.as_secs_f64()),
+            exported: current.as_ref().and_then(|c| c.cache.dbchange.exported),
+            error,
+            tables: current.as_ref().map(|c| c.cache.table_sizes().into_iter().collect()).unwrap_or_default(),
+        }
+    }
+
+    /// Loads and checks a new cache from the database path, then swaps it in.
+    /// Returns false if another reload is already running.
+    pub fn reload(&self) -> Result<bool> {
+        if self.reloading.swap(true, Ordering::SeqCst) {
+            return Ok(false);
+        }
+        let _guard = ReloadGuard(&self.reloading);
+
+        let start = Instant::now();
+        let res = load_preferring_snapshot(&self.path, &self.opts)
+            .map_err(|e| e.into())
+            .and_then(|cache| cache.check().map(|_| cache));
+        let cache = match res {
+            Ok(cache) => cache,
+            Err(e) => {
+                *self.last_error.write().unwrap() = Some(e.to_string());
+                return Err(e);
             }
-            "cve_detail" => {
-                cache
-                    .cve_detail
-                    .insert(id.parse()?, pickle::from_slice(data)?);
+        };
+
+        *self.current.write().unwrap() = Some(LoadedCache {
+            cache: Arc::new(cache),
+            loaded_at: Utc::now(),
+            load_duration: start.elapsed(),
+        });
+        *self.last_error.write().unwrap() = None;
+        Ok(true)
+    }
+
+    /// Runs `reload` on a separate thread, so the caller is not blocked
+    pub fn reload_in_background(handle: Arc<CacheHandle>) {
+        std::thread::spawn(move || {
+            info!("Reloading cache from {:?}", handle.path);
+            match handle.reload() {
+                Ok(true) => info!("Cache loaded in {:.3}s", handle.status().load_duration.unwrap_or_default()),
+                Ok(false) => warn!("Cache reload already in progress"),
+                Err(e) => error!("Cache reload failed: {}", e),
             }
-            "dbchange" => {
-                //cache.dbchange.insert(id.parse()?, pickle::from_slice(data)?);
+        });
+    }
+}
+
+/// Clears the reloading flag even if loading panics
+struct ReloadGuard<'a>(&'a AtomicBool);
+
+impl<'a> Drop for ReloadGuard<'a> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheLoadError {
+    /// Database file could not be opened
+    Open { path: PathBuf, cause: String },
+    /// Key is not in `table:id` format
+    InvalidKey { key: String, cause: String },
+    /// Id or value of a row could not be decoded
+    InvalidRow { table: String, key: String, cause: Box<dyn Error + Send + Sync> },
+    /// Table is not known to this version, only reported in strict mode
+    UnknownTable { table: String, key: String },
+    /// Snapshot could not be read or written, or has an unsupported version
+    Snapshot { path: PathBuf, cause: String },
+}
+
+impl std::fmt::Display for CacheLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CacheLoadError::Open { path, cause } => write!(f, "Opening {:?} failed: {}", path, cause),
+            CacheLoadError::InvalidKey { key, cause } => write!(f, "Invalid key {:?}: {}", key, cause),
+            CacheLoadError::InvalidRow { table, key, cause } => {
+                write!(f, "Invalid row {:?} in table {:?}: {}", key, table, cause)
             }
-            "errata_detail" => {
-                cache
-                    .errata_detail
-                    .insert(id.parse()?, pickle::from_slice(data)?);
+            CacheLoadError::UnknownTable { table, key } => {
+                write!(f, "Table {:?} not implemented (key {:?})", table, key)
             }
-            "pkgerrata2module" => {
-                // TODO:
+            CacheLoadError::Snapshot { path, cause } => write!(f, "Snapshot {:?}: {}", path, cause),
+        }
+    }
+}
+
+impl Error for CacheLoadError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Log and skip tables, which are not known to this version, instead of failing
+    pub tolerant: bool,
+}
+
+pub fn load(name: impl Into<PathBuf>) -> Result<Cache, CacheLoadError> {
+    load_with_options(name, &LoadOptions::default())
+}
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"WEBAPPRS";
+/// Bump whenever layout of `Cache` changes, old snapshots are then rejected
+const SNAPSHOT_VERSION: u32 = 2;
+
+/// Path of the snapshot, which belongs to the database at `path`
+pub fn snapshot_path(path: &Path) -> PathBuf {
+    path.with_extension("snap")
+}
+
+/// Writes the cache in native format, prefixed by a versioned header.
+/// The snapshot is written next to `path` and renamed over it once complete,
+/// so a reader never sees a partially written snapshot.
+pub fn save_snapshot(cache: &Cache, path: &Path) -> Result<(), CacheLoadError> {
+    let err = |cause: String| CacheLoadError::Snapshot { path: path.to_owned(), cause };
+    let tmp_path = path.with_extension("snap.tmp");
+
+    let write = || -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, cache).map_err(|e| io::Error::other(e.to_string()))?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&tmp_path, path)
+    };
+    write().map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        err(e.to_string())
+    })
+}
+
+pub fn load_snapshot(path: &Path) -> Result<Cache, CacheLoadError> {
+    let err = |cause: String| CacheLoadError::Snapshot { path: path.to_owned(), cause };
+
+    let file = File::open(path).map_err(|e| err(e.to_string()))?;
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0u8; 8];
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut magic).map_err(|e| err(e.to_string()))?;
+    reader.read_exact(&mut version).map_err(|e| err(e.to_string()))?;
+    if &magic != SNAPSHOT_MAGIC {
+        return Err(err("not a cache snapshot".to_owned()));
+    }
+    let version = u32::from_le_bytes(version);
+    if version != SNAPSHOT_VERSION {
+        return Err(err(format!("unsupported version {}, expected {}", version, SNAPSHOT_VERSION)));
+    }
+
+    let mut cache: Cache = bincode::deserialize_from(reader).map_err(|e| err(e.to_string()))?;
+    cache.intern_strings();
+    Ok(cache)
+}
+
+/// Loads the snapshot of the database when there is one at least as new as the database,
+/// otherwise loads the database itself.
+pub fn load_preferring_snapshot(path: &Path, opts: &LoadOptions) -> Result<Cache, CacheLoadError> {
+    let snapshot = snapshot_path(path);
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+
+    if let Some(snapshot_modified) = modified(&snapshot) {
+        if modified(path).is_none_or(|db_modified| snapshot_modified >= db_modified) {
+            match load_snapshot(&snapshot) {
+                Ok(cache) => return Ok(cache),
+                Err(e) => warn!("{}, falling back to {:?}", e, path),
             }
-            "modulename2id" => {
-                //println!("Id: {:?}", id);
-                cache
-                    .modulename_to_id
-                    .insert(id.parse()?, pickle::from_slice(data)?);
+        } else {
+            info!("Snapshot {:?} is older than {:?}, ignoring it", snapshot, path);
+        }
+    }
+    load_with_options(path, opts)
+}
+
+pub fn load_with_options(name: impl Into<PathBuf>, opts: &LoadOptions) -> Result<Cache, CacheLoadError> {
+    let file = name.into();
+    let db = GdbmOpener::new()
+        .readonly(&file)
+        .map_err(|e| CacheLoadError::Open { path: file.clone(), cause: format!("{:?}", e) })?;
+
+    // Collect raw rows grouped by table, decoding happens in parallel afterwards
+    let mut tables: Map<String, Vec<(String, Vec<u8>)>> = Map::default();
+    for (key, data) in db.iter() {
+        let kstr = std::str::from_utf8(key.as_bytes()).map_err(|e| CacheLoadError::InvalidKey {
+            key: String::from_utf8_lossy(key.as_bytes()).into_owned(),
+            cause: e.to_string(),
+        })?;
+        let split = kstr.find(':').ok_or_else(|| CacheLoadError::InvalidKey {
+            key: kstr.to_owned(),
+            cause: "missing table separator".to_owned(),
+        })?;
+        let (table, id) = (&kstr[..split], &kstr[split + 1..]);
+
+        tables
+            .entry(table.to_owned())
+            .or_default()
+            .push((id.to_owned(), data.as_bytes().to_vec()));
+    }
+
+    let chunks = tables
+        .iter()
+        .flat_map(|(table, rows)| rows.chunks(LOAD_CHUNK_SIZE).map(move |rows| (table.as_str(), rows)))
+        .collect::<Vec<_>>();
+
+    let partials = chunks
+        .par_iter()
+        .map(|(table, rows)| decode_chunk(table, rows))
+        .collect::<Vec<_>>();
+
+    let mut cache = Cache::default();
+    let mut skipped_tables = Set::default();
+    for ((table, rows), partial) in chunks.iter().zip(partials) {
+        match partial? {
+            Some(partial) => cache.merge(partial),
+            None if !opts.tolerant => {
+                return Err(CacheLoadError::UnknownTable { table: table.to_string(), key: rows[0].0.clone() });
             }
-            "src_pkg_id2pkg_ids" => {
-                cache
-                    .src_pkg_id_to_pkg_ids
-                    .insert(id.parse()?, pickle::from_slice(data)?);
+            None => {
+                if skipped_tables.insert(*table) {
+                    warn!("Skipping unknown table {:?}", table);
+                }
             }
-            other => panic!("Table {:?} not implemented", other),
         }
     }
 
+    cache.intern_strings();
     Ok(cache)
 }
+
+/// Number of rows decoded together in one task
+const LOAD_CHUNK_SIZE: usize = 50_000;
+
+/// Decodes rows of one table into a partial cache, returns `None` if the table is not known
+fn decode_chunk(table: &str, rows: &[(String, Vec<u8>)]) -> Result<Option<Cache>, CacheLoadError> {
+    let mut cache = Cache::default();
+    for (id, data) in rows {
+        let known = load_row(&mut cache, table, id, data).map_err(|cause| {
+            CacheLoadError::InvalidRow { table: table.to_owned(), key: id.to_owned(), cause }
+        })?;
+        if !known {
+            return Ok(None);
+        }
+    }
+    Ok(Some(cache))
+}
+
+/// Moves entries of `other` into `map`, avoiding rehashing when `map` is still empty
+fn merge_map<K: Eq + std::hash::Hash, V>(map: &mut Map<K, V>, other: Map<K, V>) {
+    if map.is_empty() {
+        *map = other;
+    } else {
+        map.extend(other);
+    }
+}
+
+/// Inserts one row into the cache, returns false if the table is not known
+fn load_row(cache: &mut Cache, table: &str, id: &str, data: &[u8]) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    match table {
+        "packagename2id" => {
+            cache
+                .name_to_id
+                .insert(id.into(), pickle::from_slice(data)?);
+        }
+        "id2packagename" => {
+            cache
+                .id_to_name
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "updates" => {
+            cache.updates.insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "updates_index" => {
+            cache
+                .updates_index
+                .insert(id.parse()?, UpdatesIndex { data: pickle::from_slice(data)? });
+        }
+        "evr2id" => {
+            cache
+                .evr_to_id
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "id2evr" => {
+            cache
+                .id_to_evr
+                .insert(id.parse()?, pickle::from_slice::<PickleEvr>(data)?.into());
+        }
+        "arch2id" => {
+            cache
+                .arch_to_id
+                .insert(id.into(), pickle::from_slice(data)?);
+        }
+        "id2arch" => {
+            cache
+                .id_to_arch
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "arch_compat" => {
+            cache
+                .arch_compat
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "package_details" => {
+            cache
+                .pkg_details
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "nevra2pkgid" => {
+            cache
+                .nevra_to_pkgid
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "repo_detail" => {
+            let id = id.parse()?;
+            cache.repo_detail.insert(id, pickle::from_slice(data)?);
+        }
+        "repolabel2ids" => {
+            cache
+                .repolabel_to_ids
+                .insert(id.into(), pickle::from_slice(data)?);
+        }
+        "productid2repoids" => {
+            cache
+                .productid_to_repoids
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "pkgid2repoids" => {
+            cache
+                .pkgid_to_repoids
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "errataid2name" => {
+            cache
+                .errataid_to_name
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "pkgid2errataids" => {
+            cache
+                .pkgid_to_errataids
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "errataid2repoids" => {
+            cache
+                .errataid_to_repoids
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "cve_detail" => {
+            cache
+                .cve_detail
+                .insert(id.into(), pickle::from_slice(data)?);
+        }
+        "dbchange" => {
+            cache
+                .dbchange
+                .insert(id, pickle::from_slice(data)?);
+        }
+        "errata_detail" => {
+            cache
+                .errata_detail
+                .insert(id.into(), pickle::from_slice(data)?);
+        }
+        "pkgerrata2module" => {
+            cache
+                .pkgerrata_to_module
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "modulename2id" => {
+            cache
+                .modulename_to_id
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        "src_pkg_id2pkg_ids" => {
+            cache
+                .src_pkg_id_to_pkg_ids
+                .insert(id.parse()?, pickle::from_slice(data)?);
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    /// Empty directory of one test, under the system temp dir
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("webapp-rs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn set_modified(path: &Path, time: SystemTime) {
+        File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+    }
+
+    fn small_cache() -> Cache {
+        let mut cache = Cache::default();
+        cache.name_to_id.insert("bash".into(), NameId(1));
+        cache.id_to_name.insert(NameId(1), "bash".into());
+        cache.arch_to_id.insert("x86_64".into(), ArchId(1));
+        cache.id_to_arch.insert(ArchId(1), "x86_64".into());
+        cache.evr_to_id.insert(Evr(0, "4.4".into(), "1.el8".into()), EvrId(1));
+        cache.id_to_evr.insert(EvrId(1), Evr(0, "4.4".into(), "1.el8".into()));
+        cache.pkg_details.insert(PkgId(10), Package {
+            name_id: NameId(1),
+            evr_id: EvrId(1),
+            arch_id: ArchId(1),
+            summary: Some("The GNU Bourne Again shell".into()),
+            desc: None,
+            source_pkg_id: None,
+        });
+        cache.repolabel_to_ids.insert("rhel-8-baseos".into(), vec![RepoId(100)]);
+        cache.repo_detail.insert(RepoId(100), Repo {
+            label: "rhel-8-baseos".into(),
+            name: "RHEL 8 BaseOS".into(),
+            url: "https://cdn/8/x86_64/baseos".into(),
+            basearch: Some("x86_64".into()),
+            releasever: Some("8".into()),
+            product: None,
+            product_id: None,
+            revision: None,
+        });
+        cache
+    }
+
+    fn first_key<K, V>(map: &Map<K, V>) -> &K {
+        map.keys().next().unwrap()
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let dir = test_dir("roundtrip");
+        let path = dir.join("data.snap");
+        save_snapshot(&small_cache(), &path).unwrap();
+        assert!(!path.with_extension("snap.tmp").exists());
+
+        let cache = load_snapshot(&path).unwrap();
+        assert_eq!(cache.name_to_id.get("bash"), Some(&NameId(1)));
+        assert_eq!(cache.pkg_details[&PkgId(10)].evr_id, EvrId(1));
+        assert_eq!(cache.id_to_evr[&EvrId(1)], Evr(0, "4.4".into(), "1.el8".into()));
+        assert_eq!(cache.table_sizes(), small_cache().table_sizes());
+
+        // Equal strings of different tables share one allocation again
+        assert!(Arc::ptr_eq(first_key(&cache.name_to_id), &cache.id_to_name[&NameId(1)]));
+        assert!(Arc::ptr_eq(first_key(&cache.arch_to_id), &cache.id_to_arch[&ArchId(1)]));
+        assert!(Arc::ptr_eq(first_key(&cache.repolabel_to_ids), &cache.repo_detail[&RepoId(100)].label));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn snapshot_rejects_bad_header() {
+        let dir = test_dir("header");
+        let path = dir.join("data.snap");
+        save_snapshot(&small_cache(), &path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'X';
+        std::fs::write(&path, &bad_magic).unwrap();
+        match load_snapshot(&path) {
+            Err(CacheLoadError::Snapshot { cause, .. }) => assert_eq!(cause, "not a cache snapshot"),
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+
+        let mut bad_version = data;
+        bad_version[8..12].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        std::fs::write(&path, &bad_version).unwrap();
+        match load_snapshot(&path) {
+            Err(CacheLoadError::Snapshot { cause, .. }) => assert!(cause.starts_with("unsupported version"), "{}", cause),
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn snapshot_preferred_only_when_not_older() {
+        let dir = test_dir("prefer");
+        // Not a gdbm database, so falling back to it fails with `Open`
+        let db = dir.join("data.dbm");
+        std::fs::write(&db, b"not a database").unwrap();
+        let snapshot = snapshot_path(&db);
+        save_snapshot(&small_cache(), &snapshot).unwrap();
+
+        let now = SystemTime::now();
+        let earlier = now - Duration::from_secs(60);
+        let opts = LoadOptions::default();
+
+        set_modified(&db, earlier);
+        set_modified(&snapshot, now);
+        assert!(load_preferring_snapshot(&db, &opts).is_ok());
+
+        set_modified(&db, now);
+        set_modified(&snapshot, earlier);
+        assert!(matches!(load_preferring_snapshot(&db, &opts), Err(CacheLoadError::Open { .. })));
+
+        // Unreadable snapshot falls back to the database, even when newer
+        std::fs::write(&snapshot, b"garbage").unwrap();
+        set_modified(&db, earlier);
+        set_modified(&snapshot, now);
+        assert!(matches!(load_preferring_snapshot(&db, &opts), Err(CacheLoadError::Open { .. })));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
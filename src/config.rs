@@ -0,0 +1,306 @@
+use crate::prelude::*;
+use crate::cache::LoadOptions;
+
+use std::path::PathBuf;
+
+/// File read when neither `--config` nor `WEBAPP_CONFIG` is given, if it exists
+const DEFAULT_CONFIG_FILE: &str = "webapp.toml";
+const ENV_PREFIX: &str = "WEBAPP_";
+
+/// Runtime settings of the server.
+/// Each setting is taken from, in increasing priority: defaults, the TOML file,
+/// `WEBAPP_<SETTING>` environment variables and `--<setting>` command line flags.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Addresses to listen on, e.g. `127.0.0.1:8001` or `[::1]:8001`
+    pub listen: Vec<String>,
+    pub workers: usize,
+    /// Maximum number of pending connections
+    pub backlog: i32,
+    pub db_path: PathBuf,
+    /// Skip tables of the dump unknown to this version, instead of failing the load
+    pub tolerant_load: bool,
+    /// Maximum size of JSON request bodies in bytes
+    pub json_limit: usize,
+    /// Keep-alive timeout in seconds, 0 disables keep-alive
+    pub keep_alive: usize,
+    /// Filter in `RUST_LOG` syntax, e.g. `info` or `warn,webapp_rs=debug`.
+    /// When not set, `RUST_LOG` is used and `info` without it.
+    pub log_level: Option<String>,
+    /// Bearer token required by `/admin` endpoints, which are disabled without it
+    pub admin_token: Option<Secret>,
+    /// Directory with `swagger-ui.css` and `swagger-ui-bundle.js` of swagger-ui-dist,
+    /// served by `/api/vN/docs`, which is disabled without it
+    pub docs_assets: Option<PathBuf>,
+}
+
+/// Setting, which is never printed, e.g. when the config is logged
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(pub String);
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<secret>")
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            listen: vec!["127.0.0.1:8001".to_owned()],
+            workers: 1,
+            backlog: 2048,
+            db_path: PathBuf::from("data.dbm"),
+            tolerant_load: true,
+            json_limit: 1000 * 1000,
+            keep_alive: 5,
+            log_level: None,
+            admin_token: None,
+            docs_assets: None,
+        }
+    }
+}
+
+/// Names of settings, as used in the TOML file. Flags and environment variables are derived from them.
+const SETTINGS: &[&str] = &["listen", "workers", "backlog", "db_path", "tolerant_load", "json_limit", "keep_alive", "log_level", "admin_token", "docs_assets"];
+
+fn parse_setting<T: FromStr>(key: &str, value: &str) -> Result<T>
+    where T::Err: std::fmt::Display
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid value {:?} of {}: {}", value, key, e).into())
+}
+
+impl Config {
+    /// Overrides one setting from its textual value, `listen` takes a comma separated list
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "listen" => {
+                self.listen = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            }
+            "workers" => self.workers = parse_setting(key, value)?,
+            "backlog" => self.backlog = parse_setting(key, value)?,
+            "db_path" => self.db_path = PathBuf::from(value),
+            "tolerant_load" => self.tolerant_load = parse_setting(key, value)?,
+            "json_limit" => self.json_limit = parse_setting(key, value)?,
+            "keep_alive" => self.keep_alive = parse_setting(key, value)?,
+            "log_level" => self.log_level = Some(value.to_owned()).filter(|l| !l.is_empty()),
+            "admin_token" => self.admin_token = Some(Secret(value.to_owned())).filter(|t| !t.0.is_empty()),
+            "docs_assets" => self.docs_assets = Some(PathBuf::from(value)).filter(|p| !p.as_os_str().is_empty()),
+            other => return Err(format!("Unknown setting {:?}", other).into()),
+        }
+        Ok(())
+    }
+
+    pub fn from_file(path: &std::path::Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Reading config {:?} failed: {}", path, e))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid config {:?}: {}", path, e).into())
+    }
+
+    /// Log filter, which applies: `log_level`, or `RUST_LOG` when it is not set
+    pub fn log_filter(&self) -> String {
+        self.log_level
+            .clone()
+            .or_else(|| std::env::var("RUST_LOG").ok())
+            .unwrap_or_else(|| "info".to_owned())
+    }
+
+    pub fn load_options(&self) -> LoadOptions {
+        LoadOptions { tolerant: self.tolerant_load }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.listen.is_empty() {
+            return Err("At least one listen address is required".into());
+        }
+        if self.workers == 0 {
+            return Err("Number of workers must be positive".into());
+        }
+        Ok(())
+    }
+}
+
+/// Command line, split into flags and remaining positional arguments
+#[derive(Debug, Default)]
+pub struct Args {
+    flags: Vec<(String, String)>,
+    /// Positional arguments, e.g. the subcommand and its arguments
+    pub positional: Vec<String>,
+}
+
+impl Args {
+    /// Accepts `--name value` and `--name=value`, names use `-` in place of `_`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut res = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let flag = match arg.strip_prefix("--") {
+                Some(flag) => flag.replace('-', "_"),
+                None => {
+                    res.positional.push(arg);
+                    continue;
+                }
+            };
+            let (name, value) = match flag.find('=') {
+                Some(i) => (flag[..i].to_owned(), flag[i + 1..].to_owned()),
+                None => {
+                    let value = args.next().ok_or_else(|| format!("Missing value of {}", arg))?;
+                    (flag, value)
+                }
+            };
+            if name != "config" && !SETTINGS.contains(&name.as_str()) {
+                return Err(format!("Unknown flag --{}", name.replace('_', "-")).into());
+            }
+            res.flags.push((name, value));
+        }
+        Ok(res)
+    }
+
+    fn flag(&self, name: &str) -> Option<&str> {
+        self.flags.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Overrides settings from `WEBAPP_<SETTING>` variables, as returned by `var`
+fn apply_env(config: &mut Config, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+    for key in SETTINGS {
+        if let Some(value) = var(&format!("{}{}", ENV_PREFIX, key.to_uppercase())) {
+            config.set(key, &value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Overrides settings from command line flags
+fn apply_args(config: &mut Config, args: &Args) -> Result<()> {
+    // Repeated `--listen` flags add up, other flags override each other
+    let listen = args
+        .flags
+        .iter()
+        .filter(|(n, _)| n == "listen")
+        .map(|(_, v)| v.as_str())
+        .collect::<Vec<_>>();
+    if !listen.is_empty() {
+        config.set("listen", &listen.join(","))?;
+    }
+    for key in SETTINGS.iter().filter(|k| **k != "listen") {
+        if let Some(value) = args.flag(key) {
+            config.set(key, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds the configuration from the config file, environment and command line flags
+pub fn load(args: &Args) -> Result<Config> {
+    let path = args
+        .flag("config")
+        .map(str::to_owned)
+        .or_else(|| std::env::var(format!("{}CONFIG", ENV_PREFIX)).ok());
+
+    let mut config = match path {
+        Some(path) => Config::from_file(path.as_ref())?,
+        None if std::path::Path::new(DEFAULT_CONFIG_FILE).exists() => {
+            Config::from_file(DEFAULT_CONFIG_FILE.as_ref())?
+        }
+        None => Config::default(),
+    };
+
+    apply_env(&mut config, |key| std::env::var(key).ok())?;
+    apply_args(&mut config, args)?;
+
+    config.validate()?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Args> {
+        Args::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |key| vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+    }
+
+    #[test]
+    fn parse_flags() {
+        let parsed = args(&["--db-path", "a.dbm", "--json-limit=10", "convert", "x.dbm"]).unwrap();
+        assert_eq!(parsed.flag("db_path"), Some("a.dbm"));
+        assert_eq!(parsed.flag("json_limit"), Some("10"));
+        assert_eq!(parsed.positional, vec!["convert", "x.dbm"]);
+
+        let parsed = args(&["--workers", "2", "--workers=3"]).unwrap();
+        assert_eq!(parsed.flag("workers"), Some("3"));
+        assert_eq!(args(&["--log-level=debug=x"]).unwrap().flag("log_level"), Some("debug=x"));
+    }
+
+    #[test]
+    fn parse_flag_errors() {
+        assert_eq!(args(&["--unknown", "1"]).unwrap_err().to_string(), "Unknown flag --unknown");
+        assert_eq!(args(&["--db_paths=x"]).unwrap_err().to_string(), "Unknown flag --db-paths");
+        assert_eq!(args(&["--workers"]).unwrap_err().to_string(), "Missing value of --workers");
+        assert!(args(&["--config", "webapp.toml"]).is_ok());
+    }
+
+    #[test]
+    fn set_settings() {
+        let mut config = Config::default();
+        config.set("listen", " 127.0.0.1:1, ,[::1]:2 ").unwrap();
+        assert_eq!(config.listen, vec!["127.0.0.1:1", "[::1]:2"]);
+        config.set("tolerant_load", "false").unwrap();
+        assert!(!config.tolerant_load);
+        config.set("admin_token", "").unwrap();
+        assert!(config.admin_token.is_none());
+        config.set("admin_token", "t").unwrap();
+        assert_eq!(config.admin_token, Some(Secret("t".to_owned())));
+        config.set("docs_assets", "").unwrap();
+        assert!(config.docs_assets.is_none());
+
+        assert!(config.set("workers", "many").is_err());
+        assert!(config.set("unknown", "1").is_err());
+    }
+
+    #[test]
+    fn precedence() {
+        let mut config: Config = toml::from_str("workers = 2\nbacklog = 10\njson_limit = 100\nkeep_alive = 1").unwrap();
+        assert_eq!((config.workers, config.backlog, config.json_limit, config.keep_alive), (2, 10, 100, 1));
+
+        apply_env(&mut config, env(&[("WEBAPP_BACKLOG", "20"), ("WEBAPP_JSON_LIMIT", "200"), ("BACKLOG", "99")])).unwrap();
+        assert_eq!((config.workers, config.backlog, config.json_limit), (2, 20, 200));
+
+        apply_args(&mut config, &args(&["--json-limit", "300", "--keep-alive=0"]).unwrap()).unwrap();
+        assert_eq!((config.workers, config.backlog, config.json_limit, config.keep_alive), (2, 20, 300, 0));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn repeated_listen_flags_add_up() {
+        let mut config = Config::default();
+        apply_env(&mut config, env(&[("WEBAPP_LISTEN", "0.0.0.0:1")])).unwrap();
+        assert_eq!(config.listen, vec!["0.0.0.0:1"]);
+
+        apply_args(&mut config, &args(&["--listen", "127.0.0.1:2", "--listen=127.0.0.1:3,[::1]:4"]).unwrap()).unwrap();
+        assert_eq!(config.listen, vec!["127.0.0.1:2", "127.0.0.1:3", "[::1]:4"]);
+    }
+
+    #[test]
+    fn invalid_file_and_values() {
+        assert!(toml::from_str::<Config>("unknown = 1").is_err());
+        let mut config = Config::default();
+        assert!(apply_env(&mut config, env(&[("WEBAPP_WORKERS", "x")])).is_err());
+        config.set("workers", "0").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,22 @@
+use crate::prelude::*;
+use crate::cache::CacheHandle;
+
+/// Liveness, the process is able to answer requests
+fn healthz_handler() -> HttpResponse {
+    HttpResponse::Ok().body("ok")
+}
+
+/// Readiness, the cache is loaded and its last reload did not fail
+fn readyz_handler(cache: Data<CacheHandle>) -> HttpResponse {
+    let status = cache.status();
+    if status.ready {
+        HttpResponse::Ok().json(status)
+    } else {
+        HttpResponse::ServiceUnavailable().json(status)
+    }
+}
+
+pub fn register(app: &mut ServiceConfig) {
+    app.service(web::resource("/healthz").route(web::get().to(healthz_handler)));
+    app.service(web::resource("/readyz").route(web::get().to(readyz_handler)));
+}
@@ -18,35 +18,104 @@ use std::sync::{Arc, RwLock};
 use regex::Match;
 
 use std::iter::FromIterator;
+use std::path::{Path, PathBuf};
 
 pub mod prelude;
 pub mod apis;
 pub mod cache;
+pub mod admin;
+pub mod rpm;
+pub mod config;
+pub mod metrics;
+pub mod health;
 
 use crate::prelude::*;
-use crate::cache::Cache;
-use crate::apis::Api;
+use crate::cache::{Cache, CacheHandle, LoadOptions};
+use crate::config::{Args, Config};
+use crate::apis::{Api, ApiVersion};
 use crate::apis::updates::UpdatesApi;
+use crate::apis::cve::CveApi;
+use crate::apis::errata::ErrataApi;
+use crate::apis::repos::ReposApi;
+use crate::apis::packages::PackagesApi;
+use crate::apis::vulnerabilities::VulnerabilitiesApi;
+use crate::apis::pkgtree::PkgTreeApi;
+use crate::apis::patches::PatchesApi;
+use crate::apis::srpm_pkgtree::SrpmPkgTreeApi;
 
+fn register_apis(c: &mut ServiceConfig, version: ApiVersion, docs_assets: &Option<PathBuf>) {
+    UpdatesApi::register(c, version);
+    CveApi::register(c, version);
+    ErrataApi::register(c, version);
+    ReposApi::register(c, version);
+    PackagesApi::register(c, version);
+    VulnerabilitiesApi::register(c, version);
+    PkgTreeApi::register(c, version);
+    PatchesApi::register(c, version);
+    SrpmPkgTreeApi::register(c, version);
+    apis::dbchange::register(c);
+    apis::openapi::register(c, version, docs_assets.clone());
+}
+
+/// Converts the gdbm dump into native snapshot: `webapp-rs convert [DB] [SNAPSHOT]`,
+/// the database defaults to the configured one
+fn convert(config: &Config, args: &[String]) -> io::Result<()> {
+    let db = args.first().map(PathBuf::from).unwrap_or_else(|| config.db_path.clone());
+    let snapshot = args.get(1).map(PathBuf::from).unwrap_or_else(|| cache::snapshot_path(&db));
+
+    let cache = cache::load_with_options(&db, &config.load_options())
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    cache.check().map_err(|e| io::Error::other(e.to_string()))?;
+    cache::save_snapshot(&cache, &snapshot).map_err(|e| io::Error::other(e.to_string()))?;
+
+    println!("Converted {:?} into {:?}", db, snapshot);
+    Ok(())
+}
 
 fn main() -> std::io::Result<()> {
-    env_logger::init();
-    let cache = cache::load("data.dbm".to_string()).unwrap();
+    let args = Args::parse(std::env::args().skip(1)).map_err(|e| io::Error::other(e.to_string()))?;
+    let config = config::load(&args).map_err(|e| io::Error::other(e.to_string()))?;
+    env_logger::Builder::new().parse_filters(&config.log_filter()).init();
+
+    match args.positional.first().map(String::as_str) {
+        Some("convert") => return convert(&config, &args.positional[1..]),
+        Some(other) => return Err(io::Error::other(format!("Unknown command {:?}", other))),
+        None => {}
+    }
+    info!("Starting with {:?}", config);
 
-    let data = Data::new(RwLock::new(cache));
+    // Cache is loaded while the server already runs, `/readyz` reports when it is done
+    let data = Data::new(CacheHandle::new(config.db_path.clone(), config.load_options()));
+    CacheHandle::reload_in_background(data.clone().into_inner());
+    let json_limit = config.json_limit;
+    let admin_token = config.admin_token.clone();
+    if admin_token.is_none() {
+        warn!("No admin_token configured, /admin endpoints are disabled");
+    }
+    let docs_assets = config.docs_assets.clone();
+    if docs_assets.is_none() {
+        info!("No docs_assets configured, /api/vN/docs is disabled");
+    }
+    admin::watch_sighup(data.clone().into_inner())?;
     println!("Serving");
-    HttpServer::new(move || {
+    let mut server = HttpServer::new(move || {
         App::new()
             .wrap(actix_web::middleware::Logger::default())
             .register_data(data.clone())
-            .data(web::JsonConfig::default().limit(1 * 1000 * 1000))
-            .service(web::scope("/api/v1").configure(|c| {
-                UpdatesApi::register(c);
-            }))
+            .data(web::JsonConfig::default().limit(json_limit).error_handler(apis::json_error_handler))
+            .service(web::scope(ApiVersion::V1.prefix()).configure(|c| register_apis(c, ApiVersion::V1, &docs_assets)))
+            .service(web::scope(ApiVersion::V2.prefix()).configure(|c| register_apis(c, ApiVersion::V2, &docs_assets)))
+            .service(web::scope(ApiVersion::V3.prefix()).configure(|c| register_apis(c, ApiVersion::V3, &docs_assets)))
+            .service(web::scope("/admin").configure(|c| admin::register(c, admin_token.clone())))
+            .configure(metrics::register)
+            .configure(health::register)
     })
+    .workers(config.workers)
+    .backlog(config.backlog)
+    .keep_alive(if config.keep_alive == 0 { None } else { Some(config.keep_alive) });
 
-    .workers(1)
-    //.backlog(1)
-    .bind("127.0.0.1:8001")?
-    .run()
+    for addr in &config.listen {
+        server = server.bind(addr.as_str())?;
+    }
+    server.run()
 }
@@ -0,0 +1,149 @@
+use crate::prelude::*;
+use crate::cache::CacheHandle;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+lazy_static::lazy_static! {
+    pub static ref METRICS: Metrics = Metrics::default();
+}
+
+const DURATION_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+const PACKAGE_LIST_BUCKETS: &[f64] = &[1.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 10000.0];
+
+/// Histogram with fixed upper bounds, counts are kept cumulative as in the exposition format
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram { bounds, counts: vec![0; bounds.len()], sum: 0.0, count: 0 }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, sep, bound, count);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, sep, self.count);
+        let labels = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels) };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+struct EndpointMetrics {
+    requests: u64,
+    errors: u64,
+    duration: Histogram,
+}
+
+struct Inner {
+    endpoints: BTreeMap<&'static str, EndpointMetrics>,
+    updates_package_list: Histogram,
+}
+
+/// Process wide metrics, rendered in Prometheus text format by `/metrics`
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            inner: Mutex::new(Inner {
+                endpoints: BTreeMap::new(),
+                updates_package_list: Histogram::new(PACKAGE_LIST_BUCKETS),
+            }),
+        }
+    }
+}
+
+impl Metrics {
+    /// Records one request of an `Api::ENDPOINT_NAME`, `failed` covers all error responses
+    pub fn observe_request(&self, endpoint: &'static str, duration: Duration, failed: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        let metrics = inner.endpoints.entry(endpoint).or_insert_with(|| EndpointMetrics {
+            requests: 0,
+            errors: 0,
+            duration: Histogram::new(DURATION_BUCKETS),
+        });
+        metrics.requests += 1;
+        if failed {
+            metrics.errors += 1;
+        }
+        metrics.duration.observe(duration.as_secs_f64());
+    }
+
+    pub fn observe_updates_package_list(&self, len: usize) {
+        self.inner.lock().unwrap().updates_package_list.observe(len as f64);
+    }
+
+    /// Renders all metrics, together with the state of currently served cache
+    pub fn render(&self, cache: &CacheHandle) -> String {
+        let mut out = String::new();
+        let inner = self.inner.lock().unwrap();
+
+        let header = |out: &mut String, name: &str, kind: &str, help: &str| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        };
+
+        header(&mut out, "webapp_requests_total", "counter", "Requests processed by API endpoint");
+        for (endpoint, m) in &inner.endpoints {
+            let _ = writeln!(out, "webapp_requests_total{{endpoint=\"{}\"}} {}", endpoint, m.requests);
+        }
+        header(&mut out, "webapp_request_errors_total", "counter", "Requests of API endpoint, which failed");
+        for (endpoint, m) in &inner.endpoints {
+            let _ = writeln!(out, "webapp_request_errors_total{{endpoint=\"{}\"}} {}", endpoint, m.errors);
+        }
+        header(&mut out, "webapp_request_duration_seconds", "histogram", "Processing time of API requests");
+        for (endpoint, m) in &inner.endpoints {
+            m.duration.render(&mut out, "webapp_request_duration_seconds", &format!("endpoint=\"{}\"", endpoint));
+        }
+
+        header(&mut out, "webapp_updates_package_list_size", "histogram", "Number of packages in updates requests");
+        inner.updates_package_list.render(&mut out, "webapp_updates_package_list_size", "");
+
+        let status = cache.status();
+        header(&mut out, "webapp_cache_table_entries", "gauge", "Number of entries in each cache table");
+        for (table, len) in &status.tables {
+            let _ = writeln!(out, "webapp_cache_table_entries{{table=\"{}\"}} {}", table, len);
+        }
+        if let Some(duration) = status.load_duration {
+            header(&mut out, "webapp_cache_load_duration_seconds", "gauge", "Duration of the last successful cache load");
+            let _ = writeln!(out, "webapp_cache_load_duration_seconds {}", duration);
+        }
+        if let Some(exported) = status.exported {
+            header(&mut out, "webapp_cache_exported_timestamp_seconds", "gauge", "Export time of the served dump");
+            let _ = writeln!(out, "webapp_cache_exported_timestamp_seconds {}", exported.timestamp());
+        }
+        out
+    }
+}
+
+fn metrics_handler(cache: Data<CacheHandle>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(METRICS.render(&cache))
+}
+
+pub fn register(app: &mut ServiceConfig) {
+    app.service(web::resource("/metrics").route(web::get().to(metrics_handler)));
+}
@@ -8,9 +8,9 @@ pub use std::iter::FromIterator;
 
 pub type Result<T, E = Box<dyn Error>> = std::result::Result<T,E>;
 
+pub use chrono::{DateTime, FixedOffset};
+
 
-use regex::Regex;
-use lazy_static::lazy_static;
 use crate::cache::Evr;
 
 
@@ -18,15 +18,66 @@ pub use actix_web::web::*;
 pub use actix_web::*;
 
 
-#[derive(Debug, Deserialize,Serialize)]
+pub const DEFAULT_PAGE_SIZE: usize = 5000;
+
+/// Paging of list requests, flattened into them. Missing fields take their defaults,
+/// as serde would silently drop a flattened `Option<PagingInfo>` with any field missing.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct PagingInfo {
-    page : usize,
-    page_size : usize,
+    pub page: usize,
+    pub page_size: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PagingResp {
+    pub page: usize,
+    pub page_size: usize,
+    pub pages: usize,
+}
+
+impl Default for PagingInfo {
+    fn default() -> Self {
+        PagingInfo {
+            page: 1,
+            page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
 }
 
+impl PagingInfo {
+    /// Returns requested page of `items`, pages are numbered from 1
+    pub fn paginate<T>(paging: &PagingInfo, items: Vec<T>) -> (Vec<T>, PagingResp) {
+        let page = paging.page.max(1);
+        let page_size = if paging.page_size == 0 { DEFAULT_PAGE_SIZE } else { paging.page_size };
+        let pages = items.len().div_ceil(page_size);
 
+        let page_items = items
+            .into_iter()
+            .skip((page - 1).saturating_mul(page_size))
+            .take(page_size)
+            .collect();
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
+        (page_items, PagingResp { page, page_size, pages })
+    }
+}
+
+/// Parses dates in ISO 8601 format, as used by the dump and by clients
+pub fn parse_datetime(s: &str) -> Result<DateTime<FixedOffset>> {
+    Ok(DateTime::parse_from_rfc3339(s)?)
+}
+
+/// Parses timestamps of the dump, which may come without offset, those are taken as UTC
+pub fn parse_dump_datetime(s: &str) -> Result<DateTime<FixedOffset>, chrono::ParseError> {
+    DateTime::parse_from_rfc3339(s).or_else(|_| {
+        chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+            .map(|d| DateTime::from_utc(d, FixedOffset::east(0)))
+    })
+}
+
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Nevra {
     pub name: String,
     pub epoch: Option<String>,
@@ -54,16 +105,29 @@ impl Nevra {
 
 impl Nevra {
     pub fn evr(&self) -> Evr {
-        // TODO: FIX
-        return Evr(
-            self.epoch
-                .as_ref()
-                .unwrap_or(&"0".to_string())
-                .parse()
-                .unwrap_or(0),
+        Evr(
+            self.epoch.as_ref().and_then(|e| e.parse().ok()).unwrap_or(0),
             self.version.clone(),
             self.release.clone(),
-        );
+        )
+    }
+}
+
+/// Orders by name, then by EVR, then by arch. Epochs differing only in how they are written,
+/// e.g. missing and `0`, are ordered last, so that `Ord` agrees with `Eq`.
+impl Ord for Nevra {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.name
+            .cmp(&other.name)
+            .then_with(|| self.evr().cmp(&other.evr()))
+            .then_with(|| self.arch.cmp(&other.arch))
+            .then_with(|| self.epoch.cmp(&other.epoch))
+    }
+}
+
+impl PartialOrd for Nevra {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -92,32 +156,225 @@ impl Serialize for Nevra {
 }
 
 
-pub const PKG_NAME : &str ="([^:(/=<> ]+)";
-pub const PKG_EPOCH : &str = "([0-9]+:)?";
-pub const PKG_VERSION : &str = "([^-:(/=<> ]+)" ;
-pub const PKG_RELEASE : &str = PKG_VERSION;
-pub const PKG_ARCH : &str = "([^-:.(/=<> ]+)";
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NevraParseError {
+    pub input: String,
+    /// Byte offset in `input`, where parsing failed
+    pub position: usize,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for NevraParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Invalid NEVRA {:?} at position {}: {}", self.input, self.position, self.reason)
+    }
+}
+
+impl Error for NevraParseError {}
 
-lazy_static! {
-    static ref NEVRA_RE: Regex =
-    //Regex::new(&format!(r#"^{}-{}{}-{}\.{}$"#, PKG_NAME,PKG_EPOCH, PKG_VERSION, PKG_RELEASE, PKG_ARCH)).unwrap();
-        Regex::new(r#"^(.*)-([0-9]+:)?([^-]+)-([^-]+)\.([a-z0-9_]+)$"#).unwrap();
+/// Splits `s` at the last occurrence of `sep`, also returning its position
+fn rsplit_part(s: &str, sep: char) -> Option<(&str, usize, &str)> {
+    s.rfind(sep).map(|i| (&s[..i], i, &s[i + 1..]))
 }
 
+/// Accepts `name-version-release.arch`, `name-epoch:version-release.arch`
+/// and `epoch:name-version-release.arch`, each optionally followed by `.rpm`.
 impl FromStr for Nevra {
-    type Err = ();
+    type Err = NevraParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // TODO: Rewrite using nom parser, gonna be faster and prettier
-        if let Some(caps) = NEVRA_RE.captures(s) {
-            return Ok(Nevra {
-                name: caps.get(1).map(|x| x.as_str().to_owned()).unwrap(),
-                epoch: caps.get(2).map(|x| x.as_str().to_owned()),
-                version: caps.get(3).map(|x| x.as_str().to_owned()).unwrap(),
-                release: caps.get(4).map(|x| x.as_str().to_owned()).unwrap(),
-                arch: caps.get(5).map(|x| x.as_str().to_owned()).unwrap(),
-            });
-        }
-        Err(())
+        let err = |position: usize, reason: &'static str| NevraParseError {
+            input: s.to_owned(),
+            position,
+            reason,
+        };
+        let is_epoch = |e: &str| !e.is_empty() && e.bytes().all(|c| c.is_ascii_digit());
+
+        let body = s.strip_suffix(".rpm").unwrap_or(s);
+
+        let (rest, dot, arch) = rsplit_part(body, '.').ok_or_else(|| err(body.len(), "missing architecture"))?;
+        if arch.is_empty() {
+            return Err(err(dot + 1, "empty architecture"));
+        }
+        if let Some(i) = arch.bytes().position(|c| !(c.is_ascii_alphanumeric() || c == b'_')) {
+            return Err(err(dot + 1 + i, "invalid character in architecture"));
+        }
+
+        let (rest, dash, release) = rsplit_part(rest, '-').ok_or_else(|| err(dot, "missing release"))?;
+        if release.is_empty() {
+            return Err(err(dash + 1, "empty release"));
+        }
+        if let Some(i) = release.find(':') {
+            return Err(err(dash + 1 + i, "invalid character in release"));
+        }
+
+        let (mut name, dash, mut version) = rsplit_part(rest, '-').ok_or_else(|| err(dash, "missing version"))?;
+        let mut epoch = None;
+        let mut version_start = dash + 1;
+        if let Some(i) = version.find(':') {
+            if !is_epoch(&version[..i]) {
+                return Err(err(version_start, "invalid epoch"));
+            }
+            if version[..i].parse::<u64>().is_err() {
+                return Err(err(version_start, "epoch out of range"));
+            }
+            epoch = Some(&version[..i]);
+            version = &version[i + 1..];
+            version_start += i + 1;
+        }
+        if version.is_empty() {
+            return Err(err(version_start, "empty version"));
+        }
+        if let Some(i) = version.find(':') {
+            return Err(err(version_start + i, "invalid character in version"));
+        }
+
+        let mut name_start = 0;
+        if let Some(i) = name.find(':') {
+            if !is_epoch(&name[..i]) {
+                return Err(err(i, "invalid character in name"));
+            }
+            if epoch.is_some() {
+                return Err(err(0, "epoch given twice"));
+            }
+            if name[..i].parse::<u64>().is_err() {
+                return Err(err(0, "epoch out of range"));
+            }
+            epoch = Some(&name[..i]);
+            name = &name[i + 1..];
+            name_start = i + 1;
+        }
+        if name.is_empty() {
+            return Err(err(name_start, "empty name"));
+        }
+        if let Some(i) = name.find(':') {
+            return Err(err(name_start + i, "invalid character in name"));
+        }
+
+        Ok(Nevra {
+            name: name.to_owned(),
+            epoch: epoch.map(str::to_owned),
+            version: version.to_owned(),
+            release: release.to_owned(),
+            arch: arch.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nevra(name: &str, epoch: Option<&str>, version: &str, release: &str, arch: &str) -> Nevra {
+        Nevra {
+            name: name.to_owned(),
+            epoch: epoch.map(str::to_owned),
+            version: version.to_owned(),
+            release: release.to_owned(),
+            arch: arch.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parse_nevra() {
+        let cases = [
+            ("bash-4.4.19-8.el8_0.x86_64", nevra("bash", None, "4.4.19", "8.el8_0", "x86_64")),
+            ("bash-0:4.4.19-8.el8_0.x86_64", nevra("bash", Some("0"), "4.4.19", "8.el8_0", "x86_64")),
+            ("2:bash-4.4.19-8.el8_0.x86_64", nevra("bash", Some("2"), "4.4.19", "8.el8_0", "x86_64")),
+            ("bash-18446744073709551615:1-1.x86_64", nevra("bash", Some("18446744073709551615"), "1", "1", "x86_64")),
+            ("bash-4.4.19-8.el8_0.x86_64.rpm", nevra("bash", None, "4.4.19", "8.el8_0", "x86_64")),
+            ("bash-1:4.4.19-8.el8_0.noarch.rpm", nevra("bash", Some("1"), "4.4.19", "8.el8_0", "noarch")),
+            ("kernel-3.10.0-1062.el7.X86_64", nevra("kernel", None, "3.10.0", "1062.el7", "X86_64")),
+            ("kernel-rt-debug-3.10.0-1062.rt56.el7.x86_64", nevra("kernel-rt-debug", None, "3.10.0", "1062.rt56.el7", "x86_64")),
+            ("python3-pip-wheel-9.0.3-16.el8.noarch", nevra("python3-pip-wheel", None, "9.0.3", "16.el8", "noarch")),
+        ];
+        for (input, expected) in &cases {
+            assert_eq!(&Nevra::from_str(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_nevra_errors() {
+        let cases = [
+            ("bash", 4, "missing architecture"),
+            ("bash-4.4-1.", 11, "empty architecture"),
+            ("bash-4.4-1.x8-6", 13, "invalid character in architecture"),
+            ("bash.x86_64", 4, "missing release"),
+            ("bash-4.4.x86_64", 4, "missing version"),
+            ("bash-.x86_64", 5, "empty release"),
+            ("bash-4.4-1:2.x86_64", 10, "invalid character in release"),
+            ("bash-x:4.4-1.x86_64", 5, "invalid epoch"),
+            ("bash-99999999999999999999:1-1.x86_64", 5, "epoch out of range"),
+            ("99999999999999999999:bash-1-1.x86_64", 0, "epoch out of range"),
+            ("bash-1:-1.x86_64", 7, "empty version"),
+            ("bash-1:4:4-1.x86_64", 8, "invalid character in version"),
+            ("x:bash-4.4-1.x86_64", 1, "invalid character in name"),
+            ("1:bash-2:4.4-1.x86_64", 0, "epoch given twice"),
+            ("-4.4-1.x86_64", 0, "empty name"),
+            ("1:-4.4-1.x86_64", 2, "empty name"),
+            ("1:ba:sh-4.4-1.x86_64", 4, "invalid character in name"),
+        ];
+        for (input, position, reason) in &cases {
+            let err = Nevra::from_str(input).unwrap_err();
+            assert_eq!((err.position, err.reason), (*position, *reason), "{}", input);
+        }
+    }
+
+    #[test]
+    fn paginate() {
+        let items = (0..10).collect::<Vec<_>>();
+        let paging = |page, page_size| PagingInfo { page, page_size };
+
+        let (page, resp) = PagingInfo::paginate(&paging(2, 3), items.clone());
+        assert_eq!(page, vec![3, 4, 5]);
+        assert_eq!((resp.page, resp.page_size, resp.pages), (2, 3, 4));
+
+        let (page, resp) = PagingInfo::paginate(&paging(0, 3), items.clone());
+        assert_eq!(page, vec![0, 1, 2]);
+        assert_eq!(resp.page, 1);
+
+        let (page, resp) = PagingInfo::paginate(&paging(5, 3), items.clone());
+        assert!(page.is_empty());
+        assert_eq!((resp.page, resp.pages), (5, 4));
+
+        let (page, _) = PagingInfo::paginate(&paging(usize::MAX, usize::MAX), items);
+        assert!(page.is_empty());
+    }
+
+    #[test]
+    fn paging_partial_request() {
+        #[derive(Deserialize)]
+        struct Req {
+            #[allow(dead_code)]
+            list: Vec<String>,
+            #[serde(flatten)]
+            paging: PagingInfo,
+        }
+        let parse = |json| serde_json::from_str::<Req>(json).map(|r| (r.paging.page, r.paging.page_size));
+
+        assert_eq!(parse(r#"{"list":[],"page":3}"#).unwrap(), (3, DEFAULT_PAGE_SIZE));
+        assert_eq!(parse(r#"{"list":[],"page_size":7}"#).unwrap(), (1, 7));
+        assert_eq!(parse(r#"{"list":[]}"#).unwrap(), (1, DEFAULT_PAGE_SIZE));
+        assert!(parse(r#"{"list":[],"page":"x","page_size":3}"#).is_err());
+
+        let items = (0..10).collect::<Vec<_>>();
+        let req = serde_json::from_str::<Req>(r#"{"list":[],"page":2,"page_size":4}"#).unwrap();
+        assert_eq!(PagingInfo::paginate(&req.paging, items).0, vec![4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn dump_datetime() {
+        let utc = parse_dump_datetime("2019-06-01T12:00:00+00:00").unwrap();
+        assert_eq!(parse_dump_datetime("2019-06-01T12:00:00").unwrap(), utc);
+        assert_eq!(parse_dump_datetime("2019-06-01T12:00:00.000").unwrap(), utc);
+        assert_eq!(parse_dump_datetime("2019-06-01T14:00:00+02:00").unwrap(), utc);
+        assert!(parse_dump_datetime("2019-06-01").is_err());
+    }
+
+    #[test]
+    fn nevra_roundtrip() {
+        for input in &["bash-4.4.19-8.el8_0.x86_64", "bash-1:4.4.19-8.el8_0.x86_64"] {
+            assert_eq!(Nevra::from_str(input).unwrap().to_string(), *input);
+        }
     }
 }
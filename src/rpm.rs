@@ -0,0 +1,295 @@
+//! RPM version comparison, following `rpmvercmp` from rpmio/rpmvercmp.c
+
+use std::cmp::Ordering;
+
+fn is_separator(c: u8) -> bool {
+    !c.is_ascii_alphanumeric() && c != b'~' && c != b'^'
+}
+
+/// Splits off the leading segment of digits, or of letters when `numeric` is false
+fn split_segment(s: &[u8], numeric: bool) -> (&[u8], &[u8]) {
+    let len = s
+        .iter()
+        .take_while(|c| if numeric { c.is_ascii_digit() } else { c.is_ascii_alphabetic() })
+        .count();
+    s.split_at(len)
+}
+
+fn strip_zeros(s: &[u8]) -> &[u8] {
+    let zeros = s.iter().take_while(|c| **c == b'0').count();
+    &s[zeros..]
+}
+
+/// Compares two version or release strings the way rpm does.
+///
+/// Strings are split into alternating numeric and alphabetic segments, numeric segments
+/// are compared as numbers and are newer than alphabetic ones. `~` sorts before anything,
+/// even the end of string, while `^` sorts after the end of string but before anything else.
+pub fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let mut one = a.as_bytes();
+    let mut two = b.as_bytes();
+
+    loop {
+        while !one.is_empty() && is_separator(one[0]) {
+            one = &one[1..];
+        }
+        while !two.is_empty() && is_separator(two[0]) {
+            two = &two[1..];
+        }
+
+        if one.first() == Some(&b'~') || two.first() == Some(&b'~') {
+            if one.first() != Some(&b'~') {
+                return Ordering::Greater;
+            }
+            if two.first() != Some(&b'~') {
+                return Ordering::Less;
+            }
+            one = &one[1..];
+            two = &two[1..];
+            continue;
+        }
+
+        if one.first() == Some(&b'^') || two.first() == Some(&b'^') {
+            if one.is_empty() {
+                return Ordering::Less;
+            }
+            if two.is_empty() {
+                return Ordering::Greater;
+            }
+            if one[0] != b'^' {
+                return Ordering::Greater;
+            }
+            if two[0] != b'^' {
+                return Ordering::Less;
+            }
+            one = &one[1..];
+            two = &two[1..];
+            continue;
+        }
+
+        if one.is_empty() || two.is_empty() {
+            break;
+        }
+
+        let numeric = one[0].is_ascii_digit();
+        let (seg1, rest1) = split_segment(one, numeric);
+        let (seg2, rest2) = split_segment(two, numeric);
+
+        if seg1.is_empty() {
+            return Ordering::Less;
+        }
+        // Numeric segment is always newer than alphabetic one
+        if seg2.is_empty() {
+            return if numeric { Ordering::Greater } else { Ordering::Less };
+        }
+
+        let ord = if numeric {
+            let (seg1, seg2) = (strip_zeros(seg1), strip_zeros(seg2));
+            seg1.len().cmp(&seg2.len()).then_with(|| seg1.cmp(seg2))
+        } else {
+            seg1.cmp(seg2)
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+
+        one = rest1;
+        two = rest2;
+    }
+
+    // Whichever version still has characters left over wins
+    match (one.is_empty(), two.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, _) => Ordering::Greater,
+    }
+}
+
+/// Compares epoch, version and release, in that order
+pub fn evr_cmp(a: (u64, &str, &str), b: (u64, &str, &str)) -> Ordering {
+    a.0.cmp(&b.0)
+        .then_with(|| rpmvercmp(a.1, b.1))
+        .then_with(|| rpmvercmp(a.2, b.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Evr;
+    use crate::prelude::Nevra;
+    use std::str::FromStr;
+
+    /// Test vectors of rpm's tests/rpmvercmp.at
+    const RPMVERCMP: &[(&str, &str, i32)] = &[
+        ("1.0", "1.0", 0),
+        ("1.0", "2.0", -1),
+        ("2.0", "1.0", 1),
+        ("2.0.1", "2.0.1", 0),
+        ("2.0", "2.0.1", -1),
+        ("2.0.1", "2.0", 1),
+        ("2.0.1a", "2.0.1a", 0),
+        ("2.0.1a", "2.0.1", 1),
+        ("2.0.1", "2.0.1a", -1),
+        ("5.5p1", "5.5p1", 0),
+        ("5.5p1", "5.5p2", -1),
+        ("5.5p2", "5.5p1", 1),
+        ("5.5p10", "5.5p10", 0),
+        ("5.5p1", "5.5p10", -1),
+        ("5.5p10", "5.5p1", 1),
+        ("10xyz", "10.1xyz", -1),
+        ("10.1xyz", "10xyz", 1),
+        ("xyz10", "xyz10", 0),
+        ("xyz10", "xyz10.1", -1),
+        ("xyz10.1", "xyz10", 1),
+        ("xyz.4", "xyz.4", 0),
+        ("xyz.4", "8", -1),
+        ("8", "xyz.4", 1),
+        ("xyz.4", "2", -1),
+        ("2", "xyz.4", 1),
+        ("5.5p2", "5.6p1", -1),
+        ("5.6p1", "5.5p2", 1),
+        ("5.6p1", "6.5p1", -1),
+        ("6.5p1", "5.6p1", 1),
+        ("6.0.rc1", "6.0", 1),
+        ("6.0", "6.0.rc1", -1),
+        ("10b2", "10a1", 1),
+        ("10a2", "10b2", -1),
+        ("1.0aa", "1.0aa", 0),
+        ("1.0a", "1.0aa", -1),
+        ("1.0aa", "1.0a", 1),
+        ("10.0001", "10.0001", 0),
+        ("10.0001", "10.1", 0),
+        ("10.1", "10.0001", 0),
+        ("10.0001", "10.0039", -1),
+        ("10.0039", "10.0001", 1),
+        ("4.999.9", "5.0", -1),
+        ("5.0", "4.999.9", 1),
+        ("20101121", "20101121", 0),
+        ("20101121", "20101122", -1),
+        ("20101122", "20101121", 1),
+        ("2_0", "2_0", 0),
+        ("2.0", "2_0", 0),
+        ("2_0", "2.0", 0),
+        ("a", "a", 0),
+        ("a+", "a+", 0),
+        ("a+", "a_", 0),
+        ("a_", "a+", 0),
+        ("+a", "+a", 0),
+        ("+a", "_a", 0),
+        ("_a", "+a", 0),
+        ("+_", "+_", 0),
+        ("_+", "+_", 0),
+        ("_+", "_+", 0),
+        ("+", "_", 0),
+        ("_", "+", 0),
+        ("1.0~rc1", "1.0~rc1", 0),
+        ("1.0~rc1", "1.0", -1),
+        ("1.0", "1.0~rc1", 1),
+        ("1.0~rc1", "1.0~rc2", -1),
+        ("1.0~rc2", "1.0~rc1", 1),
+        ("1.0~rc1~git123", "1.0~rc1~git123", 0),
+        ("1.0~rc1~git123", "1.0~rc1", -1),
+        ("1.0~rc1", "1.0~rc1~git123", 1),
+        ("1.0^", "1.0^", 0),
+        ("1.0^", "1.0", 1),
+        ("1.0", "1.0^", -1),
+        ("1.0^git1", "1.0^git1", 0),
+        ("1.0^git1", "1.0", 1),
+        ("1.0", "1.0^git1", -1),
+        ("1.0^git1", "1.0^git2", -1),
+        ("1.0^git2", "1.0^git1", 1),
+        ("1.0^git1", "1.01", -1),
+        ("1.01", "1.0^git1", 1),
+        ("1.0^20160101", "1.0^20160101", 0),
+        ("1.0^20160101", "1.0.1", -1),
+        ("1.0.1", "1.0^20160101", 1),
+        ("1.0^20160101^git1", "1.0^20160101^git1", 0),
+        ("1.0^20160102", "1.0^20160101^git1", 1),
+        ("1.0^20160101^git1", "1.0^20160102", -1),
+        ("1.0~rc1^git1", "1.0~rc1^git1", 0),
+        ("1.0~rc1^git1", "1.0~rc1", 1),
+        ("1.0~rc1", "1.0~rc1^git1", -1),
+        ("1.0^git1~pre", "1.0^git1~pre", 0),
+        ("1.0^git1", "1.0^git1~pre", 1),
+        ("1.0^git1~pre", "1.0^git1", -1),
+    ];
+
+    fn ordering(expected: i32) -> Ordering {
+        expected.cmp(&0)
+    }
+
+    #[test]
+    fn rpmvercmp_vectors() {
+        for (a, b, expected) in RPMVERCMP {
+            assert_eq!(rpmvercmp(a, b), ordering(*expected), "rpmvercmp({:?}, {:?})", a, b);
+        }
+    }
+
+    #[test]
+    fn rpmvercmp_numeric_segments() {
+        assert_eq!(rpmvercmp("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(rpmvercmp("1.9", "1.10"), Ordering::Less);
+        assert_eq!(rpmvercmp("1.001", "1.1"), Ordering::Equal);
+        assert_eq!(rpmvercmp("1.1", "1.a"), Ordering::Greater);
+    }
+
+    #[test]
+    fn evr_cmp_precedence() {
+        assert_eq!(evr_cmp((1, "1.0", "1"), (0, "2.0", "1")), Ordering::Greater);
+        assert_eq!(evr_cmp((0, "1.0", "2"), (0, "1.0", "10")), Ordering::Less);
+        assert_eq!(evr_cmp((0, "1.10", "1"), (0, "1.9", "9")), Ordering::Greater);
+    }
+
+    #[test]
+    fn evr_ord() {
+        let evr = |e, v: &str, r: &str| Evr(e, v.to_owned(), r.to_owned());
+        assert_eq!(evr(0, "1.01", "1").rpm_cmp(&evr(0, "1.1", "1")), Ordering::Equal);
+        assert_ne!(evr(0, "1.01", "1"), evr(0, "1.1", "1"));
+        assert_eq!(evr(0, "1.01", "1").cmp(&evr(0, "1.1", "1")), Ordering::Less);
+        assert_eq!(evr(0, "1.1", "1").cmp(&evr(0, "1.01", "1")), Ordering::Greater);
+        assert_eq!(evr(0, "1.1", "1").cmp(&evr(0, "1.1", "1")), Ordering::Equal);
+        assert!(evr(0, "1.10", "1") > evr(0, "1.9", "1"));
+        assert!(evr(1, "0.1", "1") > evr(0, "9.9", "9"));
+        assert!(evr(0, "1.0~rc1", "1") < evr(0, "1.0", "1"));
+    }
+
+    #[test]
+    fn nevra_ord() {
+        let nevra = |s: &str| Nevra::from_str(s).unwrap();
+        assert!(nevra("bash-0:4.10-1.el8.x86_64") > nevra("bash-0:4.9-1.el8.x86_64"));
+        assert!(nevra("bash-4.10-1.el8.x86_64") > nevra("bash-0:4.9-1.el8.x86_64"));
+        assert!(nevra("bash-1:1.0-1.el8.x86_64") > nevra("bash-0:4.9-1.el8.x86_64"));
+        assert!(nevra("a-9.0-1.el8.x86_64") < nevra("bash-1.0-1.el8.x86_64"));
+        assert!(nevra("bash-1.0-1.el8.i686") < nevra("bash-1.0-1.el8.x86_64"));
+        assert!(nevra("bash-1.01-1.el8.x86_64") < nevra("bash-1.1-1.el8.x86_64"));
+        assert!(nevra("bash-1.0-1.el8.x86_64") < nevra("bash-0:1.0-1.el8.x86_64"));
+        assert!(nevra("bash-1.0-1.el8.x86_64") < nevra("bash-1.0-2.el8.x86_64"));
+        assert!(nevra("bash-0:1.0-1.el8.x86_64") < nevra("bash-1.0-2.el8.x86_64"));
+    }
+
+    /// `Ord` has to agree with `Eq`, `Equal` only for equal values
+    #[test]
+    fn ord_agrees_with_eq() {
+        let nevras = [
+            "bash-1.01-1.el8.x86_64",
+            "bash-1.1-1.el8.x86_64",
+            "bash-0:1.1-1.el8.x86_64",
+            "bash-1.1-01.el8.x86_64",
+            "bash-1.1-1.el8.i686",
+        ]
+        .iter()
+        .map(|s| Nevra::from_str(s).unwrap())
+        .collect::<Vec<_>>();
+        for a in &nevras {
+            for b in &nevras {
+                assert_eq!(a.cmp(b) == Ordering::Equal, a == b, "{} {}", a.to_string(), b.to_string());
+                assert_eq!(a.evr().cmp(&b.evr()) == Ordering::Equal, a.evr() == b.evr());
+                assert_eq!(a.cmp(b), b.cmp(a).reverse());
+            }
+        }
+    }
+}